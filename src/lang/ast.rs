@@ -0,0 +1,94 @@
+//
+// Created by Justin Tunheim on 3/24/25
+//
+
+use crate::lang::intern::Symbol;
+use crate::lang::scan::{Span, TokenStr};
+
+/* Literal text is kept as scanned (radix prefixes, `_` separators, escapes already resolved);
+ * turning it into values is left to whatever consumes the tree */
+#[derive(Debug, PartialEq)]
+pub enum Literal<'src> {
+	Integer(TokenStr<'src>),
+	Float(TokenStr<'src>),
+	String(TokenStr<'src>),
+	Char(char),
+	True,
+	False,
+	Nil,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+	Negate,
+	Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+	Add,
+	Subtract,
+	Multiply,
+	Divide,
+	Mod,
+	Equal,
+	NotEqual,
+	Less,
+	LessEqual,
+	Greater,
+	GreaterEqual,
+	And,
+	Or,
+	BitAnd,
+	BitOr,
+	BitXor,
+	ShiftLeft,
+	ShiftRight,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ExprKind<'src> {
+	Literal(Literal<'src>),
+	Variable(Symbol),
+	Grouping(Box<Expr<'src>>),
+	Unary { op: UnaryOp, operand: Box<Expr<'src>> },
+	Binary { op: BinaryOp, left: Box<Expr<'src>>, right: Box<Expr<'src>> },
+	/* `thing = v` has no op; `thing /= v` carries Divide */
+	Assign { target: Symbol, op: Option<BinaryOp>, value: Box<Expr<'src>> },
+}
+
+/* span covers the whole expression, from its first token to its last */
+#[derive(Debug, PartialEq)]
+pub struct Expr<'src> {
+	pub kind: ExprKind<'src>,
+	pub span: Span,
+}
+
+/* Dropped with an explicit stack rather than recursively through the boxes: `1 + 1 + ...`
+ * parses as a left spine as long as the input, which would overflow the stack on drop */
+impl Drop for Expr<'_> {
+	fn drop(&mut self) {
+		let mut pending = Vec::new();
+		detach(&mut self.kind, &mut pending);
+		while let Some(mut expr) = pending.pop() {
+			detach(&mut expr.kind, &mut pending);
+		}
+	}
+}
+
+/* Moves kind's children onto pending, leaving it childless so dropping it can't recurse */
+fn detach<'src>(kind: &mut ExprKind<'src>, pending: &mut Vec<Box<Expr<'src>>>) {
+	match std::mem::replace(kind, ExprKind::Literal(Literal::Nil)) {
+		ExprKind::Grouping(inner)            => pending.push(inner),
+		ExprKind::Unary { operand, .. }      => pending.push(operand),
+		ExprKind::Binary { left, right, .. } => pending.extend([left, right]),
+		ExprKind::Assign { value, .. }       => pending.push(value),
+		ExprKind::Literal(_) | ExprKind::Variable(_) => {}
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Stmt<'src> {
+	/* `expr;` */
+	Expr(Expr<'src>),
+}
@@ -0,0 +1,55 @@
+//
+// Created by Justin Tunheim on 3/24/25
+//
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/* Cheap handle to an interned name; only meaningful to the table that made it */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+	names: Vec<Rc<str>>,
+	ids:   HashMap<Rc<str>, Symbol>,
+}
+
+impl SymbolTable {
+	pub fn intern(&mut self, name: &str) -> Symbol {
+		if let Some(sym) = self.ids.get(name) {
+			return *sym;
+		}
+		let sym  = Symbol(self.names.len() as u32);
+		let name = Rc::<str>::from(name);
+		self.names.push(name.clone());
+		self.ids.insert(name, sym);
+		sym
+	}
+
+	pub fn get(&self, name: &str) -> Option<Symbol> {
+		self.ids.get(name).copied()
+	}
+
+	pub fn resolve(&self, sym: Symbol) -> &str {
+		&self.names[sym.0 as usize]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+		use super::*;
+
+		#[test]
+		fn test_intern() {
+			let mut symbols = SymbolTable::default();
+			let thing = symbols.intern("thing");
+			let other = symbols.intern("other");
+			assert_eq!(symbols.intern("thing"), thing);
+			assert_ne!(thing, other);
+			assert_eq!(symbols.resolve(thing), "thing");
+			assert_eq!(symbols.resolve(other), "other");
+			assert_eq!(symbols.get("other"), Some(other));
+			assert_eq!(symbols.get("missing"), None);
+		}
+}
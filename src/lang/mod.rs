@@ -2,7 +2,9 @@
 // Created by Justin Tunheim on 3/24/25
 //
 
-const Extension: &str = "lang";
+pub const EXTENSION: &str = "lang";
 
+pub mod ast;
+pub mod intern;
+pub mod parse;
 pub mod scan;
-
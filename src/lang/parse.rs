@@ -0,0 +1,539 @@
+//
+// Created by Justin Tunheim on 3/24/25
+//
+
+use std::fmt;
+use crate::lang::ast::{BinaryOp, Expr, ExprKind, Literal, Stmt, UnaryOp};
+use crate::lang::intern::SymbolTable;
+use crate::lang::scan::{self, ScanError, Span, SpannedToken, Token};
+
+#[derive(Debug)]
+pub enum Error {
+	Scan(Vec<ScanError>),
+	Parse(Vec<ParseError>),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::Scan(errors)  => write_lines(f, errors),
+			Error::Parse(errors) => write_lines(f, errors),
+		}
+	}
+}
+
+impl std::error::Error for Error {}
+
+fn write_lines<E: fmt::Display>(f: &mut fmt::Formatter, errors: &[E]) -> fmt::Result {
+	for (i, error) in errors.iter().enumerate() {
+		if i > 0 {
+			writeln!(f)?;
+		}
+		write!(f, "{}", error)?;
+	}
+	Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ErrorKind {
+	ExpectedExpression,
+	ExpectedSemicolon,
+	ExpectedRightParen,
+	InvalidAssignmentTarget,
+	AssignmentInExpression,
+	ChainedComparison,
+	TooDeeplyNested,
+}
+
+impl fmt::Display for ErrorKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			ErrorKind::ExpectedExpression      => "expected an expression",
+			ErrorKind::ExpectedSemicolon       => "expected ';' after expression",
+			ErrorKind::ExpectedRightParen      => "expected ')' to close '('",
+			ErrorKind::InvalidAssignmentTarget => "only a variable can be assigned to",
+			ErrorKind::AssignmentInExpression  => "assignment can't be used as a value; did you mean '=='?",
+			ErrorKind::ChainedComparison       => "comparisons can't be chained; write `a < b && b < c`",
+			ErrorKind::TooDeeplyNested         => "expression is nested too deeply",
+		})
+	}
+}
+
+/* A syntax error at the token where parsing went wrong; parsing resumes at the next statement */
+#[derive(Debug)]
+pub struct ParseError {
+	pub kind: ErrorKind,
+	pub span: Span,
+}
+
+impl fmt::Display for ParseError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}:{}: {}", self.span.line, self.span.column, self.kind)
+	}
+}
+
+impl std::error::Error for ParseError {}
+
+/* Binding power of each binary operator, loosest first; all are left-associative except
+ * equality and comparison (see non_associative()).
+ * Bitwise operators bind tighter than comparisons, so `x & 1 == 0` is `(x & 1) == 0` */
+fn binary_op(token: &Token) -> Option<(BinaryOp, u8)> {
+	let op = match token {
+		Token::Or           => (BinaryOp::Or, 1),
+		Token::And          => (BinaryOp::And, 2),
+		Token::EqualEqual   => (BinaryOp::Equal, 3),
+		Token::BangEqual    => (BinaryOp::NotEqual, 3),
+		Token::Less         => (BinaryOp::Less, 4),
+		Token::LessEqual    => (BinaryOp::LessEqual, 4),
+		Token::Greater      => (BinaryOp::Greater, 4),
+		Token::GreaterEqual => (BinaryOp::GreaterEqual, 4),
+		Token::Pipe         => (BinaryOp::BitOr, 5),
+		Token::Caret        => (BinaryOp::BitXor, 6),
+		Token::Ampersand    => (BinaryOp::BitAnd, 7),
+		Token::ShiftLeft    => (BinaryOp::ShiftLeft, 8),
+		Token::ShiftRight   => (BinaryOp::ShiftRight, 8),
+		Token::Plus         => (BinaryOp::Add, 9),
+		Token::Minus        => (BinaryOp::Subtract, 9),
+		Token::Star         => (BinaryOp::Multiply, 10),
+		Token::Slash        => (BinaryOp::Divide, 10),
+		Token::Mod          => (BinaryOp::Mod, 10),
+		_                   => return None,
+	};
+	Some(op)
+}
+
+/* `a < b < c` would compare a bool with c, so chaining these is an error rather than
+ * silently grouping left */
+fn non_associative(op: BinaryOp) -> bool {
+	matches!(op, BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::LessEqual
+		| BinaryOp::Greater | BinaryOp::GreaterEqual)
+}
+
+/* Some(None) for a plain `=`, Some(Some(op)) for a compound assignment */
+fn assign_op(token: &Token) -> Option<Option<BinaryOp>> {
+	match token {
+		Token::Equal      => Some(None),
+		Token::PlusEqual  => Some(Some(BinaryOp::Add)),
+		Token::MinusEqual => Some(Some(BinaryOp::Subtract)),
+		Token::StarEqual  => Some(Some(BinaryOp::Multiply)),
+		Token::SlashEqual => Some(Some(BinaryOp::Divide)),
+		_                 => None,
+	}
+}
+
+fn is_trivia(token: &Token) -> bool {
+	matches!(token, Token::Comment(_) | Token::DocComment(_) | Token::InnerDocComment(_) | Token::Shebang(_))
+}
+
+/* From the start of first through the end of last */
+fn join(first: Span, last: Span) -> Span {
+	Span { len: last.byte_offset + last.len - first.byte_offset, ..first }
+}
+
+/* Far deeper than real code nests, and shallow enough that the recursion can't overflow
+ * even a debug build's stack */
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'src> {
+	/* tokens stay put and the cursor is an index, so a checkpoint is just a saved `current` */
+	tokens:  Vec<SpannedToken<'src>>,
+	current: usize,
+	errors:  Vec<ParseError>,
+	/* parentheses, prefix operators and chained assignments currently open */
+	depth:  usize,
+}
+
+impl<'src> Parser<'src> {
+	fn peek(&self) -> Option<&Token<'src>> {
+		self.tokens.get(self.current).map(|t| &t.token)
+	}
+
+	/* Only called after peek() has seen a token */
+	fn advance(&mut self) -> &SpannedToken<'src> {
+		self.current += 1;
+		&self.tokens[self.current - 1]
+	}
+
+	/* Span of the last token consumed */
+	fn previous(&self) -> Span {
+		match self.current.checked_sub(1) {
+			Some(i) => self.tokens[i].span,
+			None    => Span { line: 1, column: 1, byte_offset: 0, len: 0 },
+		}
+	}
+
+	fn match_token(&mut self, expected: &Token) -> bool {
+		if self.peek() != Some(expected) {
+			return false;
+		}
+		self.current += 1;
+		true
+	}
+
+	fn at_end(&self) -> bool {
+		matches!(self.peek(), None | Some(Token::End))
+	}
+
+	/* Reported at the next token, or just past the last one */
+	fn error(&mut self, kind: ErrorKind) {
+		let span = match self.tokens.get(self.current) {
+			Some(token) => token.span,
+			None        => {
+				let last = self.previous();
+				Span { byte_offset: last.byte_offset + last.len, len: 0, ..last }
+			}
+		};
+		self.errors.push(ParseError { kind, span });
+	}
+
+	/* Runs parse one level deeper, refusing past MAX_DEPTH instead of recursing */
+	fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+		if self.depth == MAX_DEPTH {
+			self.error(ErrorKind::TooDeeplyNested);
+			return None;
+		}
+		self.depth += 1;
+		let parsed = parse(self);
+		self.depth -= 1;
+		parsed
+	}
+
+	/* Skip the rest of a broken statement, through its `;` */
+	fn synchronize(&mut self) {
+		while !self.at_end() {
+			if self.advance().token == Token::Semicolon {
+				return;
+			}
+		}
+	}
+
+	fn statement(&mut self) -> Option<Stmt<'src>> {
+		let expr = self.expression()?;
+		if !self.match_token(&Token::Semicolon) {
+			self.error(ErrorKind::ExpectedSemicolon);
+			return None;
+		}
+		Some(Stmt::Expr(expr))
+	}
+
+	fn expression(&mut self) -> Option<Expr<'src>> {
+		self.assignment()
+	}
+
+	/* Right-associative, so `a = b = 1` assigns b first */
+	fn assignment(&mut self) -> Option<Expr<'src>> {
+		let target = self.binary(1)?;
+		let Some(op) = self.peek().and_then(assign_op) else {
+			return Some(target);
+		};
+		self.advance();
+
+		/* reported before parsing the value so errors stay in source order; the statement
+		 * still parses, so later errors in it are reported too */
+		let ExprKind::Variable(symbol) = target.kind else {
+			self.errors.push(ParseError { kind: ErrorKind::InvalidAssignmentTarget, span: target.span });
+			return self.nested(Self::assignment);
+		};
+		let value = self.nested(Self::assignment)?;
+		let span  = join(target.span, value.span);
+		Some(Expr { kind: ExprKind::Assign { target: symbol, op, value: Box::new(value) }, span })
+	}
+
+	/* Precedence climbing over binary_op(): only operators binding at least min_power are taken */
+	fn binary(&mut self, min_power: u8) -> Option<Expr<'src>> {
+		let mut left = self.unary()?;
+		/* power of the non-associative operator last folded at this level, if any */
+		let mut compared = None;
+		while let Some((op, power)) = self.peek().and_then(binary_op) {
+			if power < min_power {
+				break;
+			}
+			if non_associative(op) {
+				/* reported, then parsed as usual so the rest of the statement is still checked */
+				if compared == Some(power) {
+					self.error(ErrorKind::ChainedComparison);
+				}
+				compared = Some(power);
+			}
+			self.advance();
+			let right = self.binary(power + 1)?;
+			let span  = join(left.span, right.span);
+			left = Expr { kind: ExprKind::Binary { op, left: Box::new(left), right: Box::new(right) }, span };
+		}
+		Some(left)
+	}
+
+	fn unary(&mut self) -> Option<Expr<'src>> {
+		let op = match self.peek() {
+			Some(Token::Minus) => UnaryOp::Negate,
+			Some(Token::Bang)  => UnaryOp::Not,
+			_                  => return self.primary(),
+		};
+		let start   = self.advance().span;
+		let operand = self.nested(Self::unary)?;
+		let span    = join(start, operand.span);
+		Some(Expr { kind: ExprKind::Unary { op, operand: Box::new(operand) }, span })
+	}
+
+	fn primary(&mut self) -> Option<Expr<'src>> {
+		match self.peek() {
+			Some(Token::LeftParen) => return self.grouping(),
+			Some(Token::Integer(_) | Token::Float(_) | Token::String(_) | Token::Char(_)
+				| Token::True | Token::False | Token::Nil | Token::Identifier(_)) => {}
+			_ => {
+				self.error(ErrorKind::ExpectedExpression);
+				return None;
+			}
+		}
+
+		/* literal text is cloned rather than moved out so the tokens stay rewindable; text
+		 * borrowed from the source clones as a pointer copy */
+		let SpannedToken { token, span } = self.advance();
+		let span = *span;
+		let kind = match token {
+			Token::Integer(s)     => ExprKind::Literal(Literal::Integer(s.clone())),
+			Token::Float(s)       => ExprKind::Literal(Literal::Float(s.clone())),
+			Token::String(s)      => ExprKind::Literal(Literal::String(s.clone())),
+			Token::Char(c)        => ExprKind::Literal(Literal::Char(*c)),
+			Token::True           => ExprKind::Literal(Literal::True),
+			Token::False          => ExprKind::Literal(Literal::False),
+			Token::Nil            => ExprKind::Literal(Literal::Nil),
+			Token::Identifier(id) => ExprKind::Variable(*id),
+			_                     => unreachable!("primary token was checked by peek"),
+		};
+		Some(Expr { kind, span })
+	}
+
+	fn grouping(&mut self) -> Option<Expr<'src>> {
+		let open  = self.advance().span;
+		/* assignment is only a statement, so `(x = 1)` is almost always a mistyped `==` */
+		let inner = self.nested(|p| p.binary(1))?;
+		if self.peek().and_then(assign_op).is_some() {
+			self.error(ErrorKind::AssignmentInExpression);
+			return None;
+		}
+		if !self.match_token(&Token::RightParen) {
+			self.error(ErrorKind::ExpectedRightParen);
+			return None;
+		}
+		let span = join(open, self.previous());
+		Some(Expr { kind: ExprKind::Grouping(Box::new(inner)), span })
+	}
+}
+
+/* Parses a scanned token stream into statements, reporting every syntax error rather than
+ * the first. Comment and shebang tokens are skipped; interpolated strings aren't parsed yet */
+pub fn tokens<'src>(mut tokens: Vec<SpannedToken<'src>>) -> Result<Vec<Stmt<'src>>, Vec<ParseError>> {
+	tokens.retain(|t| !is_trivia(&t.token));
+
+	let mut parser = Parser { tokens, current: 0, errors: Vec::new(), depth: 0 };
+	let mut statements = Vec::new();
+	while !parser.at_end() {
+		match parser.statement() {
+			Some(statement) => statements.push(statement),
+			None            => parser.synchronize(),
+		}
+	}
+
+	if !parser.errors.is_empty() {
+		return Err(parser.errors);
+	}
+	Ok(statements)
+}
+
+/* Scans then parses input; scan errors stop it before parsing */
+pub fn source<'src>(input: &'src str, symbols: &mut SymbolTable) -> Result<Vec<Stmt<'src>>, Error> {
+	let scanned = scan::source(input, symbols).map_err(Error::Scan)?;
+	tokens(scanned).map_err(Error::Parse)
+}
+
+#[cfg(test)]
+mod tests {
+		use super::*;
+		use crate::lang::intern::Symbol;
+		use crate::lang::scan::TokenStr;
+
+		fn integer(text: &str) -> ExprKind<'_> {
+			ExprKind::Literal(Literal::Integer(TokenStr::from(text)))
+		}
+
+		/* Drops spans so trees can be compared by shape */
+		#[derive(Debug, PartialEq)]
+		enum Shape {
+			Integer(String),
+			Variable(Symbol),
+			Group(Box<Shape>),
+			Unary(UnaryOp, Box<Shape>),
+			Binary(BinaryOp, Box<Shape>, Box<Shape>),
+			Assign(Symbol, Option<BinaryOp>, Box<Shape>),
+			Other,
+		}
+
+		fn shape(expr: &Expr) -> Shape {
+			match &expr.kind {
+				ExprKind::Literal(Literal::Integer(s))  => Shape::Integer(s.to_string()),
+				ExprKind::Variable(symbol)              => Shape::Variable(*symbol),
+				ExprKind::Grouping(inner)               => Shape::Group(Box::new(shape(inner))),
+				ExprKind::Unary { op, operand }         => Shape::Unary(*op, Box::new(shape(operand))),
+				ExprKind::Binary { op, left, right }    => Shape::Binary(*op, Box::new(shape(left)), Box::new(shape(right))),
+				ExprKind::Assign { target, op, value }  => Shape::Assign(*target, *op, Box::new(shape(value))),
+				ExprKind::Literal(_)                    => Shape::Other,
+			}
+		}
+
+		fn shapes(input: &str, symbols: &mut SymbolTable) -> Vec<Shape> {
+			match source(input, symbols) {
+				Ok(stmts) => stmts.iter().map(|Stmt::Expr(e)| shape(e)).collect(),
+				Err(_)    => { assert_eq!(true, false); Vec::new() }
+			}
+		}
+
+		fn int(text: &str) -> Box<Shape> {
+			Box::new(Shape::Integer(String::from(text)))
+		}
+
+		fn error_kinds(input: &str) -> Vec<ErrorKind> {
+			match source(input, &mut SymbolTable::default()) {
+				Err(Error::Parse(errs)) => errs.into_iter().map(|e| e.kind).collect(),
+				_                       => Vec::new(),
+			}
+		}
+
+		#[test]
+		fn test_arithmetic() {
+			let mut symbols = SymbolTable::default();
+			let stmts = match scan::file("tests/add.lang", &mut symbols).map(|s| tokens(s.tokens)) {
+				Ok(Ok(stmts)) => stmts,
+				_             => return assert_eq!(true, false),
+			};
+			let Stmt::Expr(expr) = &stmts[0];
+			let ExprKind::Binary { op, left, right } = &expr.kind else {
+				return assert_eq!(true, false);
+			};
+			assert_eq!(*op, BinaryOp::Multiply);
+			assert_eq!(left.kind, integer("13"));
+			assert_eq!(right.kind, integer("5"));
+			assert_eq!(expr.span, Span { line: 1, column: 1, byte_offset: 0, len: 6 });
+		}
+
+		#[test]
+		fn test_precedence() {
+			let mut symbols = SymbolTable::default();
+			let x = symbols.intern("x");
+			assert_eq!(shapes("1 + 2 * 3 - 4; -(1 + 2) * x; x & 1 == 0;", &mut symbols), vec![
+				Shape::Binary(BinaryOp::Subtract,
+					Box::new(Shape::Binary(BinaryOp::Add, int("1"),
+						Box::new(Shape::Binary(BinaryOp::Multiply, int("2"), int("3"))))),
+					int("4")),
+				Shape::Binary(BinaryOp::Multiply,
+					Box::new(Shape::Unary(UnaryOp::Negate,
+						Box::new(Shape::Group(Box::new(Shape::Binary(BinaryOp::Add, int("1"), int("2"))))))),
+					Box::new(Shape::Variable(x))),
+				Shape::Binary(BinaryOp::Equal,
+					Box::new(Shape::Binary(BinaryOp::BitAnd, Box::new(Shape::Variable(x)), int("1"))),
+					int("0")),
+			]);
+		}
+
+		#[test]
+		fn test_assignment() {
+			let mut symbols = SymbolTable::default();
+			let stmts = match scan::file("tests/slash.lang", &mut symbols).map(|s| tokens(s.tokens)) {
+				Ok(Ok(stmts)) => stmts,
+				_             => return assert_eq!(true, false),
+			};
+			let thing = symbols.intern("thing");
+			let found: Vec<Shape> = stmts.iter().map(|Stmt::Expr(e)| shape(e)).collect();
+			assert_eq!(found, vec![
+				Shape::Binary(BinaryOp::Divide, int("81"), int("398")),
+				Shape::Assign(thing, None, int("64")),
+				Shape::Assign(thing, Some(BinaryOp::Divide), int("18")),
+			]);
+
+			let (a, b) = (symbols.intern("a"), symbols.intern("b"));
+			assert_eq!(shapes("a = b += 1;", &mut symbols), vec![
+				Shape::Assign(a, None, Box::new(Shape::Assign(b, Some(BinaryOp::Add), int("1")))),
+			]);
+		}
+
+		#[test]
+		fn test_invalid_target() {
+			let mut symbols = SymbolTable::default();
+			let errs = match scan::file("tests/plus.lang", &mut symbols).map(|s| tokens(s.tokens)) {
+				Ok(Err(errs)) => errs,
+				_             => return assert_eq!(true, false),
+			};
+			assert_eq!(errs.len(), 1);
+			assert_eq!(errs[0].kind, ErrorKind::InvalidAssignmentTarget);
+			assert_eq!(errs[0].span, Span { line: 2, column: 1, byte_offset: 9, len: 2 });
+			assert_eq!(errs[0].to_string(), "2:1: only a variable can be assigned to");
+		}
+
+		#[test]
+		fn test_parse_recovery() {
+			assert_eq!(error_kinds("1 +; (2 * 3; 4 5; x = 1;"), vec![
+				ErrorKind::ExpectedExpression,
+				ErrorKind::ExpectedRightParen,
+				ErrorKind::ExpectedSemicolon,
+			]);
+			assert_eq!(error_kinds("x = 1"), vec![ErrorKind::ExpectedSemicolon]);
+			assert_eq!(error_kinds("(x = 1) + 2; y * (x += 1); (x) = 1;"), vec![
+				ErrorKind::AssignmentInExpression,
+				ErrorKind::AssignmentInExpression,
+				ErrorKind::InvalidAssignmentTarget,
+			]);
+			let spans = match source("1 = 2 = 3;", &mut SymbolTable::default()) {
+				Err(Error::Parse(errs)) => errs.into_iter().map(|e| e.span.column).collect::<Vec<_>>(),
+				_                       => return assert_eq!(true, false),
+			};
+			assert_eq!(spans, vec![1, 5]);
+			assert_eq!(error_kinds("a < b < c; a == b != c; 1 <= 2 > 3 >= 4;"), vec![
+				ErrorKind::ChainedComparison,
+				ErrorKind::ChainedComparison,
+				ErrorKind::ChainedComparison,
+				ErrorKind::ChainedComparison,
+			]);
+			assert!(source("a < b == c < d; a == (b == c); a < b && b < c;", &mut SymbolTable::default()).is_ok());
+			assert!(matches!(source("x = #;", &mut SymbolTable::default()), Err(Error::Scan(_))));
+
+			let mut symbols = SymbolTable::default();
+			let scanned = match scan::source_with_comments("// note\n1; /* two */ 2;", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert!(matches!(tokens(scanned), Ok(stmts) if stmts.len() == 2));
+		}
+
+		#[test]
+		fn test_nesting_limit() {
+			let nest = |depth: usize, open: &str, close: &str| {
+				format!("{}1{};", open.repeat(depth), close.repeat(depth))
+			};
+			assert!(source(&nest(MAX_DEPTH, "(", ")"), &mut SymbolTable::default()).is_ok());
+			assert!(source(&nest(MAX_DEPTH, "- ", ""), &mut SymbolTable::default()).is_ok());
+
+			for input in [nest(MAX_DEPTH + 1, "(", ")"), nest(10_000, "(", ")"), nest(10_000, "- ", ""), nest(10_000, "!", "")] {
+				assert_eq!(error_kinds(&input), vec![ErrorKind::TooDeeplyNested]);
+			}
+			let chain = format!("{}1;", "x = ".repeat(10_000));
+			assert_eq!(error_kinds(&chain), vec![ErrorKind::TooDeeplyNested]);
+
+			/* parsing picks up again at the next statement */
+			assert_eq!(error_kinds(&format!("{} 1 +;", nest(500, "(", ")"))),
+				vec![ErrorKind::TooDeeplyNested, ErrorKind::ExpectedExpression]);
+		}
+
+		#[test]
+		fn test_long_chain() {
+			/* the left spine is 100k deep; parsing it and dropping it must not recurse that far */
+			let input = format!("{}1;", "1 + ".repeat(100_000));
+			let stmts = match source(&input, &mut SymbolTable::default()) {
+				Ok(stmts) => stmts,
+				Err(_)    => return assert_eq!(true, false),
+			};
+			let Stmt::Expr(expr) = &stmts[0];
+			assert!(matches!(expr.kind, ExprKind::Binary { op: BinaryOp::Add, .. }));
+			assert_eq!(expr.span.len, input.len() - 1);
+			drop(stmts);
+		}
+}
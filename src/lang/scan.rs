@@ -2,23 +2,130 @@
 // Created by Justin Tunheim on 3/21/25
 //
 
-use std::iter::Enumerate;
-use std::str::Chars;
-use byteyarn::{Yarn};
-use crate::lang;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use byteyarn::{YarnBox};
+use unicode_xid::UnicodeXID;
+use crate::lang::intern::{Symbol, SymbolTable};
 
 #[derive(Debug)]
 pub enum Error {
-	File,
+	NotFound(PathBuf),
+	PermissionDenied(PathBuf),
+	/* any other failure opening or reading the file */
+	File(PathBuf, io::Error),
+	Read(io::Error),
+	/* None when the source wasn't a file, e.g. reader() or stdin() */
+	Scan(Option<PathBuf>, Vec<ScanError>),
+}
+
+/* One diagnostic per line, in source order, as `path:line:column: message` when there's a path */
+fn write_diagnostics(f: &mut fmt::Formatter, path: Option<&Path>, errors: &[ScanError]) -> fmt::Result {
+	for (i, error) in errors.iter().enumerate() {
+		if i > 0 {
+			writeln!(f)?;
+		}
+		if let Some(path) = path {
+			write!(f, "{}:", path.display())?;
+		}
+		write!(f, "{}", error)?;
+	}
+	Ok(())
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::NotFound(path)         => write!(f, "{}: no such file", path.display()),
+			Error::PermissionDenied(path) => write!(f, "{}: permission denied", path.display()),
+			Error::File(path, e)          => write!(f, "{}: {}", path.display(), e),
+			Error::Read(e)                => write!(f, "failed to read input: {}", e),
+			Error::Scan(path, errors)     => write_diagnostics(f, path.as_deref(), errors),
+		}
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Error::File(_, e) | Error::Read(e) => Some(e),
+			_                                  => None,
+		}
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ErrorKind {
 	Terminal,
-	EndOfFile,
+	UnterminatedString,
+	UnterminatedComment,
+	InvalidEscape,
+	MalformedNumber,
+	UnterminatedChar,
+	MalformedChar,
+	InvalidUtf8,
 }
 
-/* Optimized unicode byte string */
-pub type TokenStr = Yarn;
+impl fmt::Display for ErrorKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			ErrorKind::Terminal            => "unexpected character",
+			ErrorKind::UnterminatedString  => "unterminated string",
+			ErrorKind::UnterminatedComment => "unterminated block comment",
+			ErrorKind::InvalidEscape       => "invalid escape sequence",
+			ErrorKind::MalformedNumber     => "malformed number",
+			ErrorKind::UnterminatedChar    => "unterminated character literal",
+			ErrorKind::MalformedChar       => "malformed character literal",
+			ErrorKind::InvalidUtf8         => "invalid UTF-8",
+		})
+	}
+}
+
+/* A lexical error and the char that caused it; scanning carries on past it */
+#[derive(Debug)]
+pub struct ScanError {
+	pub kind:     ErrorKind,
+	pub terminal: char,
+	pub span:     Span,
+}
+
+/* `line:column: message`, naming the offending char where it tells the user something */
+impl fmt::Display for ScanError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}:{}: {}", self.span.line, self.span.column, self.kind)?;
+		match self.kind {
+			ErrorKind::Terminal | ErrorKind::InvalidEscape | ErrorKind::MalformedNumber => {
+				write!(f, " {:?}", self.terminal)
+			}
+			_ => Ok(()),
+		}
+	}
+}
+
+impl std::error::Error for ScanError {}
+
+/* Optimized unicode byte string, borrowed from the source unless it had to be rewritten */
+pub type TokenStr<'src> = YarnBox<'src, str>;
+
+/* 1-based line/column of the first char, plus the byte range it covers */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+	pub line:        usize,
+	pub column:      usize,
+	pub byte_offset: usize,
+	pub len:         usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SpannedToken<'src> {
+	pub token: Token<'src>,
+	pub span:  Span,
+}
 
 #[derive(Debug, PartialEq)]
-pub enum Token {
+pub enum Token<'src> {
 	LeftParen,
 	RightParen,
 	LeftBrace,
@@ -50,261 +157,1910 @@ pub enum Token {
 	Less,
 	LessEqual,
 	And,
+	Or,
+	Ampersand,
+	Pipe,
+	Caret,
+	ShiftLeft,
+	ShiftRight,
+
+	If,
+	Else,
+	While,
+	For,
+	Fn,
+	Let,
+	Var,
+	Return,
+	Break,
+	Continue,
+	True,
+	False,
+	Nil,
 
-	Identifier(TokenStr),
-	String(TokenStr),
-	Number(TokenStr),
+	Identifier(Symbol),
+	String(TokenStr<'src>),
+	Integer(TokenStr<'src>),
+	Float(TokenStr<'src>),
+	Char(char),
+	Comment(TokenStr<'src>),
+	Shebang(TokenStr<'src>),
+	/* `///`, documenting what follows */
+	DocComment(TokenStr<'src>),
+	/* `//!`, documenting the enclosing item or file */
+	InnerDocComment(TokenStr<'src>),
+
+	/* "a ${b} c" scans as StringBegin, StringPart("a "), InterpolationBegin,
+	 * Identifier(b), InterpolationEnd, StringPart(" c"), StringEnd */
+	StringBegin,
+	StringPart(TokenStr<'src>),
+	InterpolationBegin,
+	InterpolationEnd,
+	StringEnd,
 
 	End,
 }
 
-fn is_identifier(terminal: &char) -> bool {
-	if terminal.is_alphabetic() || terminal.is_digit(10) {
-		return true
+impl SpannedToken<'_> {
+	pub fn into_owned(self) -> SpannedToken<'static> {
+		SpannedToken { token: self.token.into_owned(), span: self.span }
+	}
+}
+
+/* Tokens scanned from raw bytes, plus an ErrorKind::InvalidUtf8 diagnostic for each invalid
+ * sequence that was decoded as U+FFFD; invalid is empty when the input was valid UTF-8 */
+#[derive(Debug)]
+pub struct Scanned<'src> {
+	pub tokens:  Vec<SpannedToken<'src>>,
+	pub invalid: Vec<ScanError>,
+}
+
+impl Scanned<'_> {
+	pub fn into_owned(self) -> Scanned<'static> {
+		let tokens = self.tokens.into_iter().map(SpannedToken::into_owned).collect();
+		Scanned { tokens, invalid: self.invalid }
+	}
+}
+
+impl Token<'_> {
+	/* Detaches the token from its source buffer, copying any borrowed text */
+	pub fn into_owned(self) -> Token<'static> {
+		match self {
+			Token::LeftParen    => Token::LeftParen,
+			Token::RightParen   => Token::RightParen,
+			Token::LeftBrace    => Token::LeftBrace,
+			Token::RightBrace   => Token::RightBrace,
+			Token::LeftBracket  => Token::LeftBracket,
+			Token::RightBracket => Token::RightBracket,
+			Token::Comma        => Token::Comma,
+			Token::Dot          => Token::Dot,
+			Token::Colon        => Token::Colon,
+			Token::Semicolon    => Token::Semicolon,
+			Token::Slash        => Token::Slash,
+			Token::SlashEqual   => Token::SlashEqual,
+			Token::Star         => Token::Star,
+			Token::StarEqual    => Token::StarEqual,
+			Token::Mod          => Token::Mod,
+			Token::Plus         => Token::Plus,
+			Token::PlusPlus     => Token::PlusPlus,
+			Token::PlusEqual    => Token::PlusEqual,
+			Token::Minus        => Token::Minus,
+			Token::MinusMinus   => Token::MinusMinus,
+			Token::MinusEqual   => Token::MinusEqual,
+			Token::Bang         => Token::Bang,
+			Token::BangEqual    => Token::BangEqual,
+			Token::Equal        => Token::Equal,
+			Token::EqualEqual   => Token::EqualEqual,
+			Token::Greater      => Token::Greater,
+			Token::GreaterEqual => Token::GreaterEqual,
+			Token::Less         => Token::Less,
+			Token::LessEqual    => Token::LessEqual,
+			Token::And          => Token::And,
+			Token::Or           => Token::Or,
+			Token::Ampersand    => Token::Ampersand,
+			Token::Pipe         => Token::Pipe,
+			Token::Caret        => Token::Caret,
+			Token::ShiftLeft    => Token::ShiftLeft,
+			Token::ShiftRight   => Token::ShiftRight,
+			Token::If           => Token::If,
+			Token::Else         => Token::Else,
+			Token::While        => Token::While,
+			Token::For          => Token::For,
+			Token::Fn           => Token::Fn,
+			Token::Let          => Token::Let,
+			Token::Var          => Token::Var,
+			Token::Return       => Token::Return,
+			Token::Break        => Token::Break,
+			Token::Continue     => Token::Continue,
+			Token::True         => Token::True,
+			Token::False        => Token::False,
+			Token::Nil          => Token::Nil,
+			Token::End          => Token::End,
+			Token::Identifier(s) => Token::Identifier(s),
+			Token::String(s)     => Token::String(s.immortalize()),
+			Token::Integer(s)    => Token::Integer(s.immortalize()),
+			Token::Float(s)      => Token::Float(s.immortalize()),
+			Token::Char(c)       => Token::Char(c),
+			Token::StringBegin   => Token::StringBegin,
+			Token::StringPart(s) => Token::StringPart(s.immortalize()),
+			Token::InterpolationBegin => Token::InterpolationBegin,
+			Token::InterpolationEnd   => Token::InterpolationEnd,
+			Token::StringEnd     => Token::StringEnd,
+			Token::Comment(s)    => Token::Comment(s.immortalize()),
+			Token::Shebang(s)    => Token::Shebang(s.immortalize()),
+			Token::DocComment(s) => Token::DocComment(s.immortalize()),
+			Token::InnerDocComment(s) => Token::InnerDocComment(s.immortalize()),
+		}
+	}
+}
+
+/* UAX #31 identifiers: XID_Start or `_` first, then XID_Continue (which includes `_`) */
+fn is_identifier_start(terminal: char) -> bool {
+	terminal == '_' || terminal.is_xid_start()
+}
+
+fn is_identifier(terminal: char) -> bool {
+	terminal.is_xid_continue()
+}
+
+/* The default keyword table; see Options::keywords */
+pub fn keyword(ident: &str) -> Option<Token<'static>> {
+	let tok = match ident {
+		"if"       => Token::If,
+		"else"     => Token::Else,
+		"while"    => Token::While,
+		"for"      => Token::For,
+		"fn"       => Token::Fn,
+		"let"      => Token::Let,
+		"var"      => Token::Var,
+		"return"   => Token::Return,
+		"break"    => Token::Break,
+		"continue" => Token::Continue,
+		"true"     => Token::True,
+		"false"    => Token::False,
+		"nil"      => Token::Nil,
+		_          => return None,
+	};
+	Some(tok)
+}
+
+/* Lexer knobs for embedders experimenting with dialects; Default is the standard language */
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+	/* keep line and block comments (and a shebang) as tokens rather than skipping them */
+	pub emit_comments:         bool,
+	/* skip a `#!` line at the start of the source instead of erroring on `#` */
+	pub allow_shebang:         bool,
+	/* maps a scanned word to its keyword token, or None to make it an identifier */
+	pub keywords:              fn(&str) -> Option<Token<'static>>,
+	/* scan each newline outside strings and comments as Token::Semicolon */
+	pub newline_as_terminator: bool,
+}
+
+impl Default for Options {
+	fn default() -> Self {
+		Options {
+			emit_comments:         false,
+			allow_shebang:         true,
+			keywords:              keyword,
+			newline_as_terminator: false,
+		}
 	}
-	false
 }
 
-struct Scanner<'src> {
-	source: Enumerate<Chars<'src>>,
+/* Lazily scans source, yielding tokens and errors in source order; ends after Token::End */
+pub struct Scanner<'src, 'sym> {
+	input:    &'src str,
+	symbols:  &'sym mut SymbolTable,
+	/* byte offset of the next char; always on a char boundary */
+	offset:   usize,
+	line:     usize,
+	column:   usize,
+	options:  Options,
+	queued:   VecDeque<Result<SpannedToken<'src>, ScanError>>,
+	modes:    Vec<Mode>,
+	finished: bool,
+}
+
+/* Scanning is in normal mode when the stack is empty */
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+	/* inside an interpolated string, between its embedded expressions */
+	String { triple: bool, start: Span },
+	/* inside `${ ... }`; depth counts unclosed `{` so the right `}` ends it */
+	Interpolation { depth: usize },
+}
+
+enum SegmentEnd {
+	Quote,
+	Interpolation,
+	Eof,
+}
+
+fn between(start: Span, end: Span) -> Span {
+	Span { len: end.byte_offset - start.byte_offset, ..start }
 }
 
-impl<'src> Scanner<'src> {
+impl<'src, 'sym> Scanner<'src, 'sym> {
+	fn new(input: &'src str, symbols: &'sym mut SymbolTable, options: Options) -> Self {
+		Scanner {
+			input,
+			symbols,
+			offset:   0,
+			line:     1,
+			column:   1,
+			options,
+			queued:   VecDeque::new(),
+			modes:    Vec::new(),
+			finished: false,
+		}
+	}
+
+	fn advance(&mut self) -> Option<char> {
+		let terminal = self.peek()?;
+		self.offset += terminal.len_utf8();
+		if terminal == '\n' {
+			self.line  += 1;
+			self.column = 1;
+		} else {
+			self.column += 1;
+		}
+		Some(terminal)
+	}
+
+	fn mark(&self) -> Span {
+		Span { line: self.line, column: self.column, byte_offset: self.offset, len: 0 }
+	}
+
+	/* Source text from start up to the current position */
+	fn slice(&self, start: usize) -> &'src str {
+		&self.input[start..self.offset]
+	}
+
+	fn since(&self, start: Span) -> Span {
+		Span { len: self.offset - start.byte_offset, ..start }
+	}
+
+	fn spanned(&self, token: Token<'src>, start: Span) -> SpannedToken<'src> {
+		SpannedToken { token, span: self.since(start) }
+	}
+
+	fn queue(&mut self, token: Token<'src>, span: Span) {
+		self.queued.push_back(Ok(SpannedToken { token, span }));
+	}
+
+	fn error(&mut self, kind: ErrorKind, terminal: char, start: Span) {
+		let span = self.since(start);
+		self.queued.push_back(Err(ScanError { kind, terminal, span }));
+	}
+
+	/* Most source is ASCII, so only decode when the next byte starts a multi-byte char */
+	fn peek(&self) -> Option<char> {
+		match self.input.as_bytes().get(self.offset) {
+			Some(&byte) if byte.is_ascii() => Some(byte as char),
+			Some(_)                        => self.input[self.offset..].chars().next(),
+			None                           => None,
+		}
+	}
+
+	fn peek_nth(&self, n: usize) -> Option<char> {
+		self.input[self.offset..].chars().nth(n)
+	}
+
+	/* Digits of the given radix, allowing `_` separators */
+	fn digits(&mut self, radix: u32) {
+		while let Some(terminal) = self.peek() {
+			if !terminal.is_digit(radix) && terminal != '_' {
+				break;
+			}
+			self.advance();
+		}
+	}
+
 	fn match_char(&mut self, terminal: char) -> bool {
-		let Some((i, peek_term)) = self.source.next() else {
+		let Some(peek_term) = self.peek() else {
 			return false;
 		};
 		if peek_term != terminal {
 			return false; 
 		}
+		self.advance();
 		true
 	}
 
-	fn number(&mut self, init: char) -> Token {
-		let mut tok_str = String::from(init);
-		while let Some((_, terminal)) = self.source.clone().peekable().peek() {
-			if !terminal.is_digit(10) {
+	fn number(&mut self, init: char, start: Span) -> Option<Token<'src>> {
+		let radix = match (init, self.peek()) {
+			('0', Some('x' | 'X')) => 16,
+			('0', Some('o' | 'O')) => 8,
+			('0', Some('b' | 'B')) => 2,
+			_                      => 10,
+		};
+		if radix != 10 {
+			return self.radix_number(radix, start);
+		}
+
+		let mut float = false;
+		self.digits(10);
+
+		/* fractional part only if a digit follows the dot, so `1.foo` stays a member access */
+		if self.peek() == Some('.') && self.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) {
+			self.advance();
+			self.digits(10);
+			float = true;
+		}
+
+		/* exponent only if digits follow the `e` (optionally signed) */
+		if let Some('e' | 'E') = self.peek() {
+			let digit_at = match self.peek_nth(1) {
+				Some('+' | '-') => 2,
+				_               => 1,
+			};
+			if self.peek_nth(digit_at).is_some_and(|c| c.is_ascii_digit()) {
+				for _ in 0..digit_at {
+					self.advance();
+				}
+				self.digits(10);
+				float = true;
+			}
+		}
+
+		let tok_str = TokenStr::new(self.slice(start.byte_offset));
+		if float {
+			return Some(Token::Float(tok_str));
+		}
+		Some(Token::Integer(tok_str))
+	}
+
+	/* 0x, 0o and 0b literals; the token text keeps its prefix and separators */
+	fn radix_number(&mut self, radix: u32, start: Span) -> Option<Token<'src>> {
+		let prefix = self.advance().unwrap();
+		let digits = self.offset;
+		self.digits(radix);
+
+		let empty    = self.slice(digits).chars().all(|c| c == '_');
+		let trailing = self.peek().filter(|c| c.is_ascii_alphanumeric());
+		if empty || trailing.is_some() {
+			while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+				self.advance();
+			}
+			self.error(ErrorKind::MalformedNumber, trailing.unwrap_or(prefix), start);
+			return None;
+		}
+		Some(Token::Integer(TokenStr::new(self.slice(start.byte_offset))))
+	}
+
+	fn identifier(&mut self, start: Span) -> Token<'src> {
+		while let Some(terminal) = self.peek() {
+			if !is_identifier(terminal) {
 				break;
 			}
-			tok_str.push(self.source.next().unwrap().1);
+			self.advance();
+		}
+		let tok_str = self.slice(start.byte_offset);
+		(self.options.keywords)(tok_str).unwrap_or_else(|| Token::Identifier(self.symbols.intern(tok_str)))
+	}
+
+	/* A string without `${` is one Token::String. One with interpolation is queued as
+	 * StringBegin, its parts and embedded tokens, then StringEnd, via the mode stack */
+	fn string(&mut self, start: Span) -> Option<Token<'src>> {
+		let triple = self.peek() == Some('"') && self.peek_nth(1) == Some('"');
+		if triple {
+			self.advance();
+			self.advance();
+		}
+
+		let content = self.mark();
+		let (text, end, at) = self.segment(triple);
+		match end {
+			SegmentEnd::Quote => Some(Token::String(text)),
+			SegmentEnd::Eof   => {
+				self.error(ErrorKind::UnterminatedString, '"', start);
+				None
+			},
+			SegmentEnd::Interpolation => {
+				self.queue(Token::StringBegin, between(start, content));
+				self.part(text, content, at);
+				self.queue(Token::InterpolationBegin, self.since(at));
+				self.modes.push(Mode::String { triple, start });
+				self.modes.push(Mode::Interpolation { depth: 0 });
+				None
+			},
+		}
+	}
+
+	/* Picks an interpolated string back up after the `}` closing one of its expressions */
+	fn resume_string(&mut self, triple: bool, start: Span) {
+		let content = self.mark();
+		let (text, end, at) = self.segment(triple);
+		self.part(text, content, at);
+		match end {
+			SegmentEnd::Quote => {
+				self.modes.pop();
+				self.queue(Token::StringEnd, self.since(at));
+			},
+			SegmentEnd::Eof   => {
+				self.modes.clear();
+				self.error(ErrorKind::UnterminatedString, '"', start);
+			},
+			SegmentEnd::Interpolation => {
+				self.queue(Token::InterpolationBegin, self.since(at));
+				self.modes.push(Mode::Interpolation { depth: 0 });
+			},
+		}
+	}
+
+	/* Literal string text up to a closing quote or `${`, borrowed unless an escape forces a copy.
+	 * Also returns where the terminator starts. Triple-quoted strings only close on `"""` */
+	fn segment(&mut self, triple: bool) -> (TokenStr<'src>, SegmentEnd, Span) {
+		let content = self.offset;
+		let mut escaped: Option<String> = None;
+		loop {
+			let at = self.mark();
+			let end = match self.advance() {
+				None => SegmentEnd::Eof,
+				Some('"') if !triple || (self.peek() == Some('"') && self.peek_nth(1) == Some('"')) => {
+					if triple {
+						self.advance();
+						self.advance();
+					}
+					SegmentEnd::Quote
+				},
+				Some('$') if self.peek() == Some('{') => {
+					self.advance();
+					SegmentEnd::Interpolation
+				},
+				Some('\\') => {
+					let tok_str = escaped.get_or_insert_with(|| self.input[content..at.byte_offset].to_string());
+					if let Some(c) = self.escape(at) {
+						tok_str.push(c);
+					}
+					continue;
+				},
+				Some(terminal) => {
+					if let Some(tok_str) = escaped.as_mut() {
+						tok_str.push(terminal);
+					}
+					continue;
+				},
+			};
+			let text = match escaped {
+				Some(s) => TokenStr::from_string(s),
+				None    => TokenStr::new(&self.input[content..at.byte_offset]),
+			};
+			return (text, end, at);
+		}
+	}
+
+	fn part(&mut self, text: TokenStr<'src>, start: Span, end: Span) {
+		if !text.is_empty() {
+			self.queue(Token::StringPart(text), between(start, end));
+		}
+	}
+
+	/* r"..." with any number of #s around the quotes; the contents are taken verbatim */
+	fn raw_string(&mut self, start: Span) -> Option<Token<'src>> {
+		let hashes = self.hash_run();
+		for _ in 0..=hashes {
+			self.advance();
+		}
+
+		let content = self.offset;
+		loop {
+			let end = self.offset;
+			let Some(terminal) = self.advance() else {
+				self.error(ErrorKind::UnterminatedString, '"', start);
+				return None;
+			};
+			let rest = &self.input.as_bytes()[self.offset..];
+			if terminal == '"' && rest.len() >= hashes && rest[..hashes].iter().all(|&b| b == b'#') {
+				for _ in 0..hashes {
+					self.advance();
+				}
+				return Some(Token::String(TokenStr::new(&self.input[content..end])));
+			}
+		}
+	}
+
+	/* Whether an `r` just consumed opens a raw string rather than an identifier */
+	fn at_raw_string(&self) -> bool {
+		self.input.as_bytes().get(self.offset + self.hash_run()) == Some(&b'"')
+	}
+
+	/* Length of the `#` run at the current offset; `#` is ASCII, so bytes are enough and the
+	 * run is read once rather than decoded from the start for every `#` */
+	fn hash_run(&self) -> usize {
+		self.input.as_bytes()[self.offset..].iter().take_while(|&&b| b == b'#').count()
+	}
+
+	/* A bad escape is recorded and skipped so the rest of the string still scans */
+	fn escape(&mut self, start: Span) -> Option<char> {
+		let terminal = self.advance()?;
+		match terminal {
+			'n'  => Some('\n'),
+			't'  => Some('\t'),
+			'"'  => Some('"'),
+			'\'' => Some('\''),
+			'$'  => Some('$'),
+			'\\' => Some('\\'),
+			_    => {
+				self.error(ErrorKind::InvalidEscape, terminal, start);
+				None
+			},
 		}
-		Token::Number(tok_str.into())
 	}
 
-	fn identifier(&mut self, init: char) -> Token {
-		let mut tok_str = String::from(init);
-		while let Some((_, terminal)) = self.source.clone().peekable().peek() {
-			if !is_identifier(&terminal) {
+	/* Exactly one char or escape between single quotes, without crossing a line */
+	fn char(&mut self, start: Span) -> Option<Token<'src>> {
+		let at = self.mark();
+		let value = match self.advance() {
+			None | Some('\n') => {
+				self.error(ErrorKind::UnterminatedChar, '\'', start);
+				return None;
+			},
+			Some('\'')  => {
+				self.error(ErrorKind::MalformedChar, '\'', start);
+				return None;
+			},
+			Some('\\') => self.escape(at),
+			Some(c)    => Some(c),
+		};
+		if self.match_char('\'') {
+			return value.map(Token::Char);
+		}
+
+		while let Some(terminal) = self.peek() {
+			if terminal == '\n' {
 				break;
 			}
-			tok_str.push(self.source.next().unwrap().1);
+			self.advance();
+			if terminal == '\'' {
+				self.error(ErrorKind::MalformedChar, '\'', start);
+				return None;
+			}
+		}
+		self.error(ErrorKind::UnterminatedChar, '\'', start);
+		None
+	}
+
+	/* Called with the leading '/' consumed and the next char being '/' or '*' */
+	fn comment(&mut self, start: Span) -> Option<Token<'src>> {
+		let content = start.byte_offset + 2;
+		if let Some('/') = self.advance() {
+			/* `///` and `//!` are doc comments, but `////...` is just a rule */
+			let outer = self.peek() == Some('/') && self.peek_nth(1) != Some('/');
+			let inner = self.peek() == Some('!');
+			if outer || inner {
+				self.advance();
+			}
+			while self.peek().is_some() && !self.at_line_end() {
+				self.advance();
+			}
+			if outer {
+				return Some(Token::DocComment(TokenStr::new(self.slice(content + 1))));
+			}
+			if inner {
+				return Some(Token::InnerDocComment(TokenStr::new(self.slice(content + 1))));
+			}
+			return Some(Token::Comment(TokenStr::new(self.slice(content))));
+		}
+
+		let mut depth = 1;
+		loop {
+			let at = self.offset;
+			let Some(terminal) = self.advance() else {
+				self.error(ErrorKind::UnterminatedComment, '/', start);
+				return None;
+			};
+			match terminal {
+				'*' if self.peek() == Some('/') => {
+					self.advance();
+					depth -= 1;
+					if depth == 0 {
+						return Some(Token::Comment(TokenStr::new(&self.input[content..at])));
+					}
+				},
+				'/' if self.peek() == Some('*') => {
+					self.advance();
+					depth += 1;
+				},
+				_ => (),
+			}
+		}
+	}
+
+	/* A `#!` interpreter line, only recognized at the very start of the source */
+	/* At `\n` or `\r\n`, so line comments and shebangs from CRLF files don't keep the `\r` */
+	fn at_line_end(&self) -> bool {
+		match self.peek() {
+			Some('\n') => true,
+			Some('\r') => self.peek_nth(1) == Some('\n'),
+			_           => false,
+		}
+	}
+
+	fn shebang(&mut self) -> Token<'src> {
+		self.advance();
+		let content = self.offset;
+		while self.peek().is_some() && !self.at_line_end() {
+			self.advance();
+		}
+		Token::Shebang(TokenStr::new(self.slice(content)))
+	}
+
+	/* Scans one lexeme, queueing its token (if any) behind any errors found inside it */
+	fn lexeme(&mut self) {
+		if let Some(&Mode::String { triple, start }) = self.modes.last() {
+			self.resume_string(triple, start);
+			return;
+		}
+
+		let start = self.mark();
+		let Some(terminal) = self.advance() else {
+			/* ran out inside `${`, so the enclosing string never closed */
+			let open = self.modes.iter().rev().find_map(|mode| match mode {
+				Mode::String { start, .. } => Some(*start),
+				_                          => None,
+			});
+			if let Some(open) = open {
+				self.modes.clear();
+				self.error(ErrorKind::UnterminatedString, '"', open);
+			}
+			let end = self.spanned(Token::End, start);
+			self.queued.push_back(Ok(end));
+			self.finished = true;
+			return;
+		};
+
+		if terminal.is_ascii_digit() {
+			if let Some(number) = self.number(terminal, start) {
+				let number = self.spanned(number, start);
+				self.queued.push_back(Ok(number));
+			}
+			return;
+		}
+		let token = match terminal  {
+			'}' => match self.modes.last_mut() {
+				Some(Mode::Interpolation { depth: 0 }) => {
+					self.modes.pop();
+					Some(Token::InterpolationEnd)
+				},
+				Some(Mode::Interpolation { depth }) => {
+					*depth -= 1;
+					Some(Token::RightBrace)
+				},
+				_ => Some(Token::RightBrace),
+			},
+			'{' => {
+				if let Some(Mode::Interpolation { depth }) = self.modes.last_mut() {
+					*depth += 1;
+				}
+				Some(Token::LeftBrace)
+			},
+			']' => Some(Token::RightBracket),
+			'[' => Some(Token::LeftBracket),
+			')' => Some(Token::RightParen),
+			'(' => Some(Token::LeftParen),
+			'%' => Some(Token::Mod),
+			';' => Some(Token::Semicolon),
+			',' => Some(Token::Comma),
+			':' => Some(Token::Colon),
+			'.' => Some(Token::Dot),
+			'"' => self.string(start),
+			'\'' => self.char(start),
+			'r' if self.at_raw_string() => self.raw_string(start),
+			'#' if self.options.allow_shebang && start.byte_offset == 0 && self.peek() == Some('!') => {
+				let shebang = self.shebang();
+				Some(shebang).filter(|_| self.options.emit_comments)
+			},
+			'/' if matches!(self.peek(), Some('/' | '*')) => {
+				/* doc comments are always kept; tooling needs them even when other comments are skipped */
+				let comment = self.comment(start);
+				comment.filter(|c| self.options.emit_comments || matches!(c, Token::DocComment(_) | Token::InnerDocComment(_)))
+			},
+			
+			'\n' if self.options.newline_as_terminator => Some(Token::Semicolon),
+			/* `\r` is plain whitespace, so `\r\n` terminates once, on its `\n` */
+			' ' | '\t' | '\r' | '\n' => None,
+
+			_   => multi(self, terminal, start),
+		};
+		if let Some(token) = token {
+			let token = self.spanned(token, start);
+			self.queued.push_back(Ok(token));
 		}
-		Token::Identifier(tok_str.into())
 	}
 }
 
-pub fn file(path: &String) -> Result<Vec<Token>, Error> {
-	let src = match std::fs::read_to_string(path) {
+/* File tokens outlive the file's buffer, so they're detached with Token::into_owned */
+pub fn file(path: impl AsRef<Path>, symbols: &mut SymbolTable) -> Result<Scanned<'static>, Error> {
+	let path = path.as_ref();
+	let src  = match std::fs::read(path) {
 		Ok(s)  => s,
-		Err(_) => return Err(Error::File), // TODO: match on error and return specificity
+		Err(e) => return Err(file_error(path, e)),
 	};
 	
-	source(src)
+	let scanned = bytes(&src, symbols).map_err(|errors| Error::Scan(Some(path.to_path_buf()), errors))?;
+	Ok(scanned.into_owned())
+}
+
+fn file_error(path: &Path, e: io::Error) -> Error {
+	match e.kind() {
+		io::ErrorKind::NotFound         => Error::NotFound(path.to_path_buf()),
+		io::ErrorKind::PermissionDenied => Error::PermissionDenied(path.to_path_buf()),
+		_                               => Error::File(path.to_path_buf(), e),
+	}
 }
 
-pub fn source(input: String) -> Result<Vec<Token>, Error> {
-	let mut tokens  = Vec::new();
-	let mut scanner = Scanner { source: input.chars().enumerate() };
+/* Buffers everything r yields (a socket, a decompressor, ...) and scans it like file() */
+pub fn reader<R: io::Read>(mut r: R, symbols: &mut SymbolTable) -> Result<Scanned<'static>, Error> {
+	let mut src = Vec::new();
+	if let Err(e) = r.read_to_end(&mut src) {
+		return Err(Error::Read(e));
+	}
 
-	loop {
-		let Some((i, terminal)) = scanner.source.next() else {
-			tokens.push(Token::End);
-			break;
-		};
+	let scanned = bytes(&src, symbols).map_err(|errors| Error::Scan(None, errors))?;
+	Ok(scanned.into_owned())
+}
 
-		if terminal.is_numeric() {
-			tokens.push(scanner.number(terminal));
-			continue;
-		}
-		match terminal  {
-			'}' => tokens.push(Token::RightBrace),
-			'{' => tokens.push(Token::LeftBrace),
-			']' => tokens.push(Token::RightBracket),
-			'[' => tokens.push(Token::LeftBracket),
-			')' => tokens.push(Token::RightParen),
-			'(' => tokens.push(Token::LeftParen),
-			'%' => tokens.push(Token::Mod),
-			';' => tokens.push(Token::Semicolon),
-			':' => tokens.push(Token::Colon),
-			'=' => tokens.push(Token::Equal),
-			
-			' ' | '\n' => (),
+pub fn stdin(symbols: &mut SymbolTable) -> Result<Scanned<'static>, Error> {
+	reader(io::stdin().lock(), symbols)
+}
+
+/* An invalid input sequence and the U+FFFD it was decoded as */
+struct Replaced {
+	input:   usize,
+	len:     usize,
+	decoded: usize,
+	/* how many bytes shorter the input is than the decoded text, up to and including this one */
+	shrink:  usize,
+}
+
+/* Scans raw bytes. Valid UTF-8 (including all-ASCII) is scanned in place without copying.
+ * Otherwise each invalid sequence is decoded as U+FFFD, the way String::from_utf8_lossy
+ * does, and reported in Scanned::invalid; the rest still scans and its tokens are returned.
+ * Spans are mapped back to byte offsets into input, and each U+FFFD counts as one column.
+ * Only real lexical errors make this an Err, which then carries the InvalidUtf8 diagnostics too */
+pub fn bytes<'src>(input: &'src [u8], symbols: &mut SymbolTable) -> Result<Scanned<'src>, Vec<ScanError>> {
+	if let Ok(valid) = std::str::from_utf8(input) {
+		return source(valid, symbols).map(|tokens| Scanned { tokens, invalid: Vec::new() });
+	}
+
+	let mut decoded  = String::with_capacity(input.len());
+	let mut replaced = Vec::new();
+	let mut at       = 0;
+	let mut shrink   = 0;
+	for chunk in input.utf8_chunks() {
+		decoded.push_str(chunk.valid());
+		at += chunk.valid().len();
+		let len = chunk.invalid().len();
+		if len != 0 {
+			shrink += char::REPLACEMENT_CHARACTER.len_utf8() - len;
+			replaced.push(Replaced { input: at, len, decoded: decoded.len(), shrink });
+			decoded.push(char::REPLACEMENT_CHARACTER);
+			at += len;
+		}
+	}
+
+	/* offsets in decoded sit on char boundaries, so they're never inside a U+FFFD */
+	let to_input = |offset: usize| {
+		match replaced.partition_point(|r| r.decoded < offset) {
+			0      => offset,
+			before => offset - replaced[before - 1].shrink,
+		}
+	};
+	let remap = |span: Span| {
+		let start = to_input(span.byte_offset);
+		let end   = to_input(span.byte_offset + span.len);
+		Span { byte_offset: start, len: end - start, ..span }
+	};
+
+	/* replaced is in ascending order, so one forward pass finds every line and column */
+	let mut invalid = Vec::with_capacity(replaced.len());
+	let mut line    = 1;
+	let mut column  = 1;
+	let mut counted = 0;
+	for r in &replaced {
+		for terminal in decoded[counted..r.decoded].chars() {
+			if terminal == '\n' {
+				line  += 1;
+				column = 1;
+			} else {
+				column += 1;
+			}
+		}
+		let span = Span { line, column, byte_offset: r.input, len: r.len };
+		invalid.push(ScanError { kind: ErrorKind::InvalidUtf8, terminal: char::REPLACEMENT_CHARACTER, span });
+		column += 1;
+		counted = r.decoded + char::REPLACEMENT_CHARACTER.len_utf8();
+	}
+
+	let mut tokens = Vec::new();
+	let mut errors = Vec::new();
+	for result in Scanner::new(&decoded, symbols, Options::default()) {
+		match result {
+			Ok(token) => tokens.push(SpannedToken { span: remap(token.span), ..token.into_owned() }),
+			/* a U+FFFD outside a string or comment is already reported as InvalidUtf8 */
+			Err(e) if e.kind == ErrorKind::Terminal
+				&& replaced.binary_search_by_key(&e.span.byte_offset, |r| r.decoded).is_ok() => {},
+			Err(e)    => errors.push(ScanError { span: remap(e.span), ..e }),
+		}
+	}
+
+	if !errors.is_empty() {
+		errors.extend(invalid);
+		errors.sort_by_key(|e| e.span.byte_offset);
+		return Err(errors);
+	}
+	Ok(Scanned { tokens, invalid })
+}
+
+/* Scans all of input, returning every lexical error found rather than just the first */
+pub fn source<'src>(input: &'src str, symbols: &mut SymbolTable) -> Result<Vec<SpannedToken<'src>>, Vec<ScanError>> {
+	source_with(input, symbols, Options::default())
+}
+
+/* Same as source() but keeps comments in the stream as Token::Comment */
+pub fn source_with_comments<'src>(input: &'src str, symbols: &mut SymbolTable) -> Result<Vec<SpannedToken<'src>>, Vec<ScanError>> {
+	source_with(input, symbols, Options { emit_comments: true, ..Options::default() })
+}
+
+pub fn source_with<'src>(input: &'src str, symbols: &mut SymbolTable, options: Options) -> Result<Vec<SpannedToken<'src>>, Vec<ScanError>> {
+	collect(Scanner::new(input, symbols, options))
+}
+
+/* Identifiers are interned into symbols as they're scanned */
+pub fn tokens<'src, 'sym>(input: &'src str, symbols: &'sym mut SymbolTable) -> Scanner<'src, 'sym> {
+	tokens_with(input, symbols, Options::default())
+}
+
+pub fn tokens_with<'src, 'sym>(input: &'src str, symbols: &'sym mut SymbolTable, options: Options) -> Scanner<'src, 'sym> {
+	Scanner::new(input, symbols, options)
+}
 
-			_   => tokens.push(multi(&mut scanner, terminal)?),
+fn collect<'src>(scanner: Scanner<'src, '_>) -> Result<Vec<SpannedToken<'src>>, Vec<ScanError>> {
+	let mut tokens = Vec::new();
+	let mut errors = Vec::new();
+	for result in scanner {
+		match result {
+			Ok(token) => tokens.push(token),
+			Err(e)    => errors.push(e),
 		}
 	}
 
+	if !errors.is_empty() {
+		return Err(errors);
+	}
 	Ok(tokens)
 }
 
-fn multi(scanner: &mut Scanner, terminal: char) -> Result<Token, Error> {
+impl<'src> Iterator for Scanner<'src, '_> {
+	type Item = Result<SpannedToken<'src>, ScanError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while self.queued.is_empty() && !self.finished {
+			self.lexeme();
+		}
+		self.queued.pop_front()
+	}
+}
+
+fn multi<'src>(scanner: &mut Scanner<'src, '_>, terminal: char, start: Span) -> Option<Token<'src>> {
 	match terminal {
 		'=' => {
 			if scanner.match_char('=') {
-				return Ok(Token::EqualEqual);
+				return Some(Token::EqualEqual);
 			}
-			return Ok(Token::Equal);
+			return Some(Token::Equal);
 		},
 		'!' => {
 			if scanner.match_char('=') {
-				return Ok(Token::BangEqual);
+				return Some(Token::BangEqual);
 			}
-			return Ok(Token::Bang);
+			return Some(Token::Bang);
 		},
 		'*' => {
 			if scanner.match_char('=') {
-				return Ok(Token::StarEqual);
+				return Some(Token::StarEqual);
 			}
-			return Ok(Token::Star);
+			return Some(Token::Star);
 		},
 		'-' => {
+			if scanner.match_char('-') {
+				return Some(Token::MinusMinus);
+			}
 			if scanner.match_char('=') {
-				return Ok(Token::MinusEqual);
+				return Some(Token::MinusEqual);
 			}
-			return Ok(Token::Minus);
+			return Some(Token::Minus);
 		},
 		'/' => {
 			if scanner.match_char('=') {
-				return Ok(Token::SlashEqual);
+				return Some(Token::SlashEqual);
 			}
-			return Ok(Token::Slash);
+			return Some(Token::Slash);
 		},
 		'&' => {
 			if scanner.match_char('&') {
-				return Ok(Token::And);
+				return Some(Token::And);
 			}
+			return Some(Token::Ampersand);
 		},
+		'|' => {
+			if scanner.match_char('|') {
+				return Some(Token::Or);
+			}
+			return Some(Token::Pipe);
+		},
+		'^' => return Some(Token::Caret),
 		'+' => {
+			if scanner.match_char('+') {
+				return Some(Token::PlusPlus);
+			}
+			if scanner.match_char('=') {
+				return Some(Token::PlusEqual);
+			}
+			return Some(Token::Plus);
+		},
+		'<' => {
+			if scanner.match_char('<') {
+				return Some(Token::ShiftLeft);
+			}
+			if scanner.match_char('=') {
+				return Some(Token::LessEqual);
+			}
+			return Some(Token::Less);
+		},
+		'>' => {
+			if scanner.match_char('>') {
+				return Some(Token::ShiftRight);
+			}
 			if scanner.match_char('=') {
-				return Ok(Token::PlusEqual);
+				return Some(Token::GreaterEqual);
 			}
-			return Ok(Token::Plus);
+			return Some(Token::Greater);
 		},
 		_   => (),
 	}
 	
-	if terminal.is_alphabetic() {
-		return Ok(scanner.identifier(terminal));
+	if is_identifier_start(terminal) {
+		return Some(scanner.identifier(start));
 	}
-	Err(Error::Terminal)
+	scanner.error(ErrorKind::Terminal, terminal, start);
+	None
 }
 
 #[cfg(test)]
 mod tests {
 		use super::*;
+		use crate::lang;
 
-		fn do_file(filename: &str) -> Result<Vec<Token>, Error> {
+		fn do_file(filename: &str, symbols: &mut SymbolTable) -> Result<Vec<Token<'static>>, Error> {
 			let mut path = String::from("tests/");
 			path.push_str(filename);
-			path.push_str(".");
-			path.push_str(lang::Extension);
+			path.push('.');
+			path.push_str(lang::EXTENSION);
+
+			Ok(file(&path, symbols)?.tokens.into_iter().map(|t| t.token).collect())
+		}
 
-			file(&path)
+		fn error_kinds(input: &str) -> Vec<ErrorKind> {
+			match source(input, &mut SymbolTable::default()) {
+				Ok(_)     => Vec::new(),
+				Err(errs) => errs.into_iter().map(|e| e.kind).collect(),
+			}
 		}
 
 		#[test]
 		fn test_add() {
+			let mut symbols = SymbolTable::default();
 			let correct_toks = vec![
-				Token::Number(TokenStr::from("13")),
+				Token::Integer(TokenStr::from("13")),
 				Token::Star,
-				Token::Number(TokenStr::from("5")),
+				Token::Integer(TokenStr::from("5")),
 				Token::Semicolon,
 				Token::End,
 			];
-			let file_toks = match do_file("add") {
+			let file_toks = match do_file("add", &mut symbols) {
 				Ok(ts) => ts,
-				Err(e) => return assert_eq!(true, false),
+				Err(_) => return assert_eq!(true, false),
 			};
 			assert_eq!(file_toks, correct_toks)
 		}
 
 		#[test]
 		fn test_minus() {
+			let mut symbols = SymbolTable::default();
 			let correct_toks = vec![
-				Token::Number(TokenStr::from("24")),
+				Token::Integer(TokenStr::from("24")),
 				Token::Minus,
-				Token::Number(TokenStr::from("12")),
+				Token::Integer(TokenStr::from("12")),
 				Token::Semicolon,
 				Token::End,
 			];
-			let file_toks = match do_file("minus") {
+			let file_toks = match do_file("minus", &mut symbols) {
 				Ok(ts) => ts,
-				Err(e) => return assert_eq!(true, false),
+				Err(_) => return assert_eq!(true, false),
 			};
 			assert_eq!(file_toks, correct_toks)
 		}
 
 		#[test]
 		fn test_plus() {
+			let mut symbols = SymbolTable::default();
 			let correct_toks = vec![
-				Token::Number(TokenStr::from("78")),
+				Token::Integer(TokenStr::from("78")),
 				Token::Plus,
-				Token::Number(TokenStr::from("12")),
+				Token::Integer(TokenStr::from("12")),
 				Token::Semicolon,
-				Token::Number(TokenStr::from("23")),
+				Token::Integer(TokenStr::from("23")),
 				Token::PlusEqual,
-				Token::Number(TokenStr::from("98")),
+				Token::Integer(TokenStr::from("98")),
 				Token::Semicolon,
 				Token::End,
 			];
-			let file_toks = match do_file("plus") {
+			let file_toks = match do_file("plus", &mut symbols) {
 				Ok(ts) => ts,
-				Err(e) => return assert_eq!(true, false),
+				Err(_) => return assert_eq!(true, false),
 			};
 			assert_eq!(file_toks, correct_toks)
 		}
 
 		#[test]
 		fn test_star() {
+			let mut symbols = SymbolTable::default();
 			let correct_toks = vec![
-				Token::Number(TokenStr::from("19")),
+				Token::Integer(TokenStr::from("19")),
 				Token::Star,
-				Token::Number(TokenStr::from("73")),
+				Token::Integer(TokenStr::from("73")),
 				Token::Semicolon,
-				Token::Number(TokenStr::from("38")),
+				Token::Integer(TokenStr::from("38")),
 				Token::StarEqual,
-				Token::Number(TokenStr::from("27")),
+				Token::Integer(TokenStr::from("27")),
 				Token::Semicolon,
 				Token::End,
 			];
-			let file_toks = match do_file("star") {
+			let file_toks = match do_file("star", &mut symbols) {
 				Ok(ts) => ts,
-				Err(e) => return assert_eq!(true, false),
+				Err(_) => return assert_eq!(true, false),
 			};
 			assert_eq!(file_toks, correct_toks)
 		}
 
 		#[test]
 		fn test_slash() {
+			let mut symbols = SymbolTable::default();
 			let correct_toks = vec![
-				Token::Number(TokenStr::from("81")),
+				Token::Integer(TokenStr::from("81")),
 				Token::Slash,
-				Token::Number(TokenStr::from("398")),
+				Token::Integer(TokenStr::from("398")),
 				Token::Semicolon,
-				Token::Identifier(TokenStr::from("thing")),
+				Token::Identifier(symbols.intern("thing")),
 				Token::Equal,
-				Token::Number(TokenStr::from("64")),
+				Token::Integer(TokenStr::from("64")),
 				Token::Semicolon,
-				Token::Identifier(TokenStr::from("thing")),
+				Token::Identifier(symbols.intern("thing")),
 				Token::SlashEqual,
-				Token::Number(TokenStr::from("18")),
+				Token::Integer(TokenStr::from("18")),
 				Token::Semicolon,
 				Token::End
 			];
-			let file_toks = match do_file("slash") {
+			let file_toks = match do_file("slash", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(file_toks, correct_toks)
+		}
+
+		#[test]
+		fn test_string() {
+			let mut symbols = SymbolTable::default();
+			let correct_toks = vec![
+				Token::Identifier(symbols.intern("greeting")),
+				Token::Equal,
+				Token::String(TokenStr::from("hello\tworld")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("quote")),
+				Token::Equal,
+				Token::String(TokenStr::from("say \"hi\" \\ bye\n")),
+				Token::Semicolon,
+				Token::End,
+			];
+			let file_toks = match do_file("string", &mut symbols) {
 				Ok(ts) => ts,
-				Err(e) => return assert_eq!(true, false),
+				Err(_) => return assert_eq!(true, false),
 			};
 			assert_eq!(file_toks, correct_toks)
 		}
+
+		#[test]
+		fn test_unterminated_string() {
+			assert_eq!(error_kinds("\"abc"), vec![ErrorKind::UnterminatedString]);
+			assert_eq!(error_kinds("\"abc\\"), vec![ErrorKind::UnterminatedString]);
+			assert_eq!(error_kinds("\"a\\qc\""), vec![ErrorKind::InvalidEscape]);
+		}
+
+		#[test]
+		fn test_comment() {
+			let mut symbols = SymbolTable::default();
+			let correct_toks = vec![
+				Token::Identifier(symbols.intern("thing")),
+				Token::Equal,
+				Token::Integer(TokenStr::from("4")),
+				Token::Slash,
+				Token::Integer(TokenStr::from("2")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("thing")),
+				Token::StarEqual,
+				Token::Integer(TokenStr::from("3")),
+				Token::Semicolon,
+				Token::End,
+			];
+			let file_toks = match do_file("comment", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(file_toks, correct_toks)
+		}
+
+		#[test]
+		fn test_comment_emitted() {
+			let correct_toks = vec![
+				Token::Comment(TokenStr::from(" line")),
+				Token::Integer(TokenStr::from("1")),
+				Token::Comment(TokenStr::from(" outer /* inner */ ")),
+				Token::Semicolon,
+				Token::End,
+			];
+			let toks = match source_with_comments("// line\n1 /* outer /* inner */ */;", &mut SymbolTable::default()) {
+				Ok(ts) => ts.into_iter().map(|t| t.token).collect::<Vec<_>>(),
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(toks, correct_toks);
+			assert_eq!(error_kinds("/* open /* nested */"), vec![ErrorKind::UnterminatedComment]);
+		}
+
+		#[test]
+		fn test_float() {
+			let mut symbols = SymbolTable::default();
+			let correct_toks = vec![
+				Token::Identifier(symbols.intern("pi")),
+				Token::Equal,
+				Token::Float(TokenStr::from("3.14")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("small")),
+				Token::Equal,
+				Token::Float(TokenStr::from("1.5e-3")),
+				Token::Star,
+				Token::Float(TokenStr::from("2E8")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("whole")),
+				Token::Equal,
+				Token::Integer(TokenStr::from("7")),
+				Token::Dot,
+				Token::Identifier(symbols.intern("e")),
+				Token::Semicolon,
+				Token::End,
+			];
+			let file_toks = match do_file("float", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(file_toks, correct_toks)
+		}
+
+		#[test]
+		fn test_keyword() {
+			let mut symbols = SymbolTable::default();
+			let correct_toks = vec![
+				Token::Let,
+				Token::Identifier(symbols.intern("iffy")),
+				Token::Equal,
+				Token::True,
+				Token::Semicolon,
+				Token::If,
+				Token::Identifier(symbols.intern("iffy")),
+				Token::LeftBrace,
+				Token::Return,
+				Token::Nil,
+				Token::Semicolon,
+				Token::RightBrace,
+				Token::Else,
+				Token::LeftBrace,
+				Token::While,
+				Token::False,
+				Token::LeftBrace,
+				Token::Break,
+				Token::Semicolon,
+				Token::RightBrace,
+				Token::RightBrace,
+				Token::End,
+			];
+			let file_toks = match do_file("keyword", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(file_toks, correct_toks)
+		}
+
+		#[test]
+		fn test_span() {
+			let toks = match source("thing = \"é\";\n  thing /= 18;", &mut SymbolTable::default()) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			let spans: Vec<Span> = toks.iter().map(|t| t.span).collect();
+			assert_eq!(spans, vec![
+				Span { line: 1, column: 1,  byte_offset: 0,  len: 5 },
+				Span { line: 1, column: 7,  byte_offset: 6,  len: 1 },
+				Span { line: 1, column: 9,  byte_offset: 8,  len: 4 },
+				Span { line: 1, column: 12, byte_offset: 12, len: 1 },
+				Span { line: 2, column: 3,  byte_offset: 16, len: 5 },
+				Span { line: 2, column: 9,  byte_offset: 22, len: 2 },
+				Span { line: 2, column: 12, byte_offset: 25, len: 2 },
+				Span { line: 2, column: 14, byte_offset: 27, len: 1 },
+				Span { line: 2, column: 15, byte_offset: 28, len: 0 },
+			]);
+		}
+
+		#[test]
+		fn test_comparison() {
+			let mut symbols = SymbolTable::default();
+			let correct_toks = vec![
+				Token::Identifier(symbols.intern("x")),
+				Token::Less,
+				Token::Integer(TokenStr::from("5")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("x")),
+				Token::LessEqual,
+				Token::Integer(TokenStr::from("5")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("y")),
+				Token::Greater,
+				Token::Identifier(symbols.intern("x")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("y")),
+				Token::GreaterEqual,
+				Token::Integer(TokenStr::from("10")),
+				Token::Semicolon,
+				Token::End,
+			];
+			let file_toks = match do_file("comparison", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(file_toks, correct_toks)
+		}
+
+		#[test]
+		fn test_bitwise() {
+			let mut symbols = SymbolTable::default();
+			let correct_toks = vec![
+				Token::Identifier(symbols.intern("a")),
+				Token::And,
+				Token::Identifier(symbols.intern("b")),
+				Token::Or,
+				Token::Identifier(symbols.intern("c")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("a")),
+				Token::Ampersand,
+				Token::Identifier(symbols.intern("b")),
+				Token::Pipe,
+				Token::Identifier(symbols.intern("c")),
+				Token::Caret,
+				Token::Identifier(symbols.intern("d")),
+				Token::Semicolon,
+				Token::Integer(TokenStr::from("1")),
+				Token::ShiftLeft,
+				Token::Integer(TokenStr::from("4")),
+				Token::ShiftRight,
+				Token::Integer(TokenStr::from("2")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("a")),
+				Token::And,
+				Token::Ampersand,
+				Token::Identifier(symbols.intern("b")),
+				Token::Or,
+				Token::Pipe,
+				Token::Identifier(symbols.intern("c")),
+				Token::ShiftLeft,
+				Token::LessEqual,
+				Token::Identifier(symbols.intern("d")),
+				Token::Semicolon,
+				Token::End,
+			];
+			let file_toks = match do_file("bitwise", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(file_toks, correct_toks)
+		}
+
+		#[test]
+		fn test_increment() {
+			let mut symbols = SymbolTable::default();
+			let correct_toks = vec![
+				Token::Identifier(symbols.intern("i")),
+				Token::PlusPlus,
+				Token::Semicolon,
+				Token::MinusMinus,
+				Token::Identifier(symbols.intern("j")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("i")),
+				Token::PlusEqual,
+				Token::Equal,
+				Token::Identifier(symbols.intern("j")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("i")),
+				Token::PlusPlus,
+				Token::Plus,
+				Token::Identifier(symbols.intern("j")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("i")),
+				Token::MinusMinus,
+				Token::MinusEqual,
+				Token::Identifier(symbols.intern("j")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("i")),
+				Token::EqualEqual,
+				Token::Identifier(symbols.intern("j")),
+				Token::Semicolon,
+				Token::End,
+			];
+			let file_toks = match do_file("increment", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(file_toks, correct_toks)
+		}
+
+		#[test]
+		fn test_error_recovery() {
+			let errs = match source("a # b;\n$ \"x\\qy\" ?", &mut SymbolTable::default()) {
+				Ok(_)     => return assert_eq!(true, false),
+				Err(errs) => errs,
+			};
+			let found: Vec<(ErrorKind, char, Span)> = errs.into_iter()
+				.map(|e| (e.kind, e.terminal, e.span))
+				.collect();
+			assert_eq!(found, vec![
+				(ErrorKind::Terminal,      '#', Span { line: 1, column: 3, byte_offset: 2,  len: 1 }),
+				(ErrorKind::Terminal,      '$', Span { line: 2, column: 1, byte_offset: 7,  len: 1 }),
+				(ErrorKind::InvalidEscape, 'q', Span { line: 2, column: 5, byte_offset: 11, len: 2 }),
+				(ErrorKind::Terminal,      '?', Span { line: 2, column: 10, byte_offset: 16, len: 1 }),
+			]);
+		}
+
+		#[test]
+		fn test_tokens_lazy() {
+			let mut symbols = SymbolTable::default();
+			let thing       = symbols.intern("thing");
+			let mut scanner = tokens("thing = 64; # \"a\\qb\"", &mut symbols);
+			let mut next = || scanner.next().map(|r| r.map(|t| t.token).map_err(|e| e.kind));
+			assert_eq!(next(), Some(Ok(Token::Identifier(thing))));
+			assert_eq!(next(), Some(Ok(Token::Equal)));
+			assert_eq!(next(), Some(Ok(Token::Integer(TokenStr::from("64")))));
+			assert_eq!(next(), Some(Ok(Token::Semicolon)));
+			assert_eq!(next(), Some(Err(ErrorKind::Terminal)));
+			assert_eq!(next(), Some(Err(ErrorKind::InvalidEscape)));
+			assert_eq!(next(), Some(Ok(Token::String(TokenStr::from("ab")))));
+			assert_eq!(next(), Some(Ok(Token::End)));
+			assert_eq!(next(), None);
+		}
+
+		#[test]
+		fn test_borrowed() {
+			/* long enough that the yarn can't just inline them */
+			let input = "x = \"a plain string literal\"; // a trailing line comment";
+			let toks  = match source_with_comments(input, &mut SymbolTable::default()) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			let borrowed_at = |tok: &Token| match tok {
+				Token::String(s) | Token::Comment(s) => s.as_str().as_ptr(),
+				_ => std::ptr::null(),
+			};
+			assert_eq!(borrowed_at(&toks[2].token), input[5..].as_ptr());
+			assert_eq!(borrowed_at(&toks[4].token), input[32..].as_ptr());
+		}
+
+		#[test]
+		fn test_radix() {
+			let correct_toks = vec![
+				Token::Integer(TokenStr::from("0x1F")),
+				Token::Plus,
+				Token::Integer(TokenStr::from("0b1010")),
+				Token::Plus,
+				Token::Integer(TokenStr::from("0o755")),
+				Token::Semicolon,
+				Token::Integer(TokenStr::from("1_000_000")),
+				Token::Star,
+				Token::Float(TokenStr::from("2_500.000_1")),
+				Token::Semicolon,
+				Token::Integer(TokenStr::from("0xdead_BEEF")),
+				Token::Semicolon,
+				Token::End,
+			];
+			let file_toks = match do_file("radix", &mut SymbolTable::default()) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(file_toks, correct_toks)
+		}
+
+		#[test]
+		fn test_malformed_radix() {
+			let errs = match source("0x; 0b102 + 0o_;", &mut SymbolTable::default()) {
+				Ok(_)     => return assert_eq!(true, false),
+				Err(errs) => errs,
+			};
+			let found: Vec<(ErrorKind, char, usize)> = errs.into_iter()
+				.map(|e| (e.kind, e.terminal, e.span.len))
+				.collect();
+			assert_eq!(found, vec![
+				(ErrorKind::MalformedNumber, 'x', 2),
+				(ErrorKind::MalformedNumber, '2', 5),
+				(ErrorKind::MalformedNumber, 'o', 3),
+			]);
+		}
+
+		#[test]
+		fn test_char() {
+			let mut symbols = SymbolTable::default();
+			let correct_toks = vec![
+				Token::Identifier(symbols.intern("letter")),
+				Token::Equal,
+				Token::Char('a'),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("newline")),
+				Token::Equal,
+				Token::Char('\n'),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("quote")),
+				Token::Equal,
+				Token::Char('\''),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("wide")),
+				Token::Equal,
+				Token::Char('é'),
+				Token::Semicolon,
+				Token::End,
+			];
+			let file_toks = match do_file("char", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(file_toks, correct_toks);
+
+			assert_eq!(error_kinds("''"), vec![ErrorKind::MalformedChar]);
+			assert_eq!(error_kinds("'ab'"), vec![ErrorKind::MalformedChar]);
+			assert_eq!(error_kinds("'a\nb;"), vec![ErrorKind::UnterminatedChar]);
+			assert_eq!(error_kinds("'\\q'"), vec![ErrorKind::InvalidEscape]);
+		}
+
+		#[test]
+		fn test_raw_string() {
+			let mut symbols = SymbolTable::default();
+			let correct_toks = vec![
+				Token::Identifier(symbols.intern("path")),
+				Token::Equal,
+				Token::String(TokenStr::from("C:\\path\\n")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("quoted")),
+				Token::Equal,
+				Token::String(TokenStr::from("say \"hi\"")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("block")),
+				Token::Equal,
+				Token::String(TokenStr::from("\nline \"one\"\n\tline two\n")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("r")),
+				Token::Equal,
+				Token::String(TokenStr::from("")),
+				Token::Semicolon,
+				Token::End,
+			];
+			let file_toks = match do_file("raw", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(file_toks, correct_toks);
+
+			assert_eq!(error_kinds("r\"open"), vec![ErrorKind::UnterminatedString]);
+			assert_eq!(error_kinds("r#\"open\""), vec![ErrorKind::UnterminatedString]);
+			/* a quote followed by too few `#` is part of the text */
+			let hashed = match source("r###\"a\"##b\"###;", &mut symbols) {
+				Ok(ts) => ts.into_iter().map(|t| t.token).collect::<Vec<_>>(),
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(hashed[0], Token::String(TokenStr::from("a\"##b")));
+			assert_eq!(error_kinds("\"\"\"open\"\""), vec![ErrorKind::UnterminatedString]);
+			let toks = match source("\"\"\"a\\t\"\"b\"\"\"", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(toks[0].token, Token::String(TokenStr::from("a\t\"\"b")));
+		}
+
+		#[test]
+		fn test_interpolation() {
+			let mut symbols = SymbolTable::default();
+			let correct_toks = vec![
+				Token::Identifier(symbols.intern("greeting")),
+				Token::Equal,
+				Token::StringBegin,
+				Token::StringPart(TokenStr::from("hello ")),
+				Token::InterpolationBegin,
+				Token::Identifier(symbols.intern("name")),
+				Token::InterpolationEnd,
+				Token::StringPart(TokenStr::from(", you are ")),
+				Token::InterpolationBegin,
+				Token::Identifier(symbols.intern("age")),
+				Token::Plus,
+				Token::Integer(TokenStr::from("1")),
+				Token::InterpolationEnd,
+				Token::StringEnd,
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("price")),
+				Token::Equal,
+				Token::String(TokenStr::from("costs ${5} $ {6}")),
+				Token::Semicolon,
+				Token::End,
+			];
+			let file_toks = match do_file("interpolation", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(file_toks, correct_toks)
+		}
+
+		#[test]
+		fn test_nested_interpolation() {
+			let mut symbols = SymbolTable::default();
+			let correct_toks = vec![
+				Token::StringBegin,
+				Token::StringPart(TokenStr::from("a")),
+				Token::InterpolationBegin,
+				Token::Identifier(symbols.intern("f")),
+				Token::LeftParen,
+				Token::StringBegin,
+				Token::InterpolationBegin,
+				Token::StringBegin,
+				Token::InterpolationBegin,
+				Token::Identifier(symbols.intern("b")),
+				Token::InterpolationEnd,
+				Token::StringPart(TokenStr::from("}")),
+				Token::StringEnd,
+				Token::InterpolationEnd,
+				Token::StringEnd,
+				Token::RightParen,
+				Token::InterpolationEnd,
+				Token::StringPart(TokenStr::from("c")),
+				Token::StringEnd,
+				Token::StringBegin,
+				Token::InterpolationBegin,
+				Token::LeftBrace,
+				Token::LeftBrace,
+				Token::Identifier(symbols.intern("x")),
+				Token::RightBrace,
+				Token::RightBrace,
+				Token::InterpolationEnd,
+				Token::StringEnd,
+				Token::End,
+			];
+			let toks = match source("\"a${f(\"${\"${b}}\"}\")}c\" \"${ {{x}} }\"", &mut symbols) {
+				Ok(ts) => ts.into_iter().map(|t| t.token).collect::<Vec<_>>(),
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(toks, correct_toks);
+
+			assert_eq!(error_kinds("\"a${b"), vec![ErrorKind::UnterminatedString]);
+			assert_eq!(error_kinds("\"a${b}c"), vec![ErrorKind::UnterminatedString]);
+			assert_eq!(error_kinds("\"a${\"${b}"), vec![ErrorKind::UnterminatedString]);
+		}
+
+		#[test]
+		fn test_unicode_identifier() {
+			let mut symbols = SymbolTable::default();
+			let correct_toks = vec![
+				Token::Identifier(symbols.intern("_tmp")),
+				Token::Equal,
+				Token::Identifier(symbols.intern("café")),
+				Token::Plus,
+				Token::Identifier(symbols.intern("変数")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("__init__")),
+				Token::Equal,
+				Token::Identifier(symbols.intern("x_1")),
+				Token::Star,
+				Token::Identifier(symbols.intern("_")),
+				Token::Semicolon,
+				Token::End,
+			];
+			let file_toks = match do_file("unicode", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(file_toks, correct_toks);
+
+			/* digits and combining marks may continue an identifier but never start one */
+			assert_eq!(error_kinds("\u{0301}x"), vec![ErrorKind::Terminal]);
+			assert_eq!(error_kinds("\u{0663}"), vec![ErrorKind::Terminal]);
+			assert_eq!(error_kinds("x\u{0663}\u{0301}"), vec![]);
+		}
+
+		#[test]
+		fn test_shebang() {
+			let mut symbols = SymbolTable::default();
+			let correct_toks = vec![
+				Token::Identifier(symbols.intern("thing")),
+				Token::Equal,
+				Token::Integer(TokenStr::from("1")),
+				Token::Semicolon,
+				Token::End,
+			];
+			let file_toks = match do_file("shebang", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(file_toks, correct_toks);
+
+			let toks = match source_with_comments("#!/usr/bin/env ville\n", &mut symbols) {
+				Ok(ts) => ts.into_iter().map(|t| t.token).collect::<Vec<_>>(),
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(toks, vec![Token::Shebang(TokenStr::from("/usr/bin/env ville")), Token::End]);
+
+			/* only the first line may be one */
+			assert_eq!(error_kinds("\n#!/usr/bin/env ville"), vec![ErrorKind::Terminal]);
+		}
+
+		#[test]
+		fn test_doc_comment() {
+			let mut symbols = SymbolTable::default();
+			let correct_toks = vec![
+				Token::InnerDocComment(TokenStr::from(" Thing helpers.")),
+				Token::DocComment(TokenStr::from(" Doubles the thing.")),
+				Token::DocComment(TokenStr::from("")),
+				Token::Identifier(symbols.intern("thing")),
+				Token::StarEqual,
+				Token::Integer(TokenStr::from("2")),
+				Token::Semicolon,
+				Token::End,
+			];
+			let file_toks = match do_file("doc", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(file_toks, correct_toks);
+
+			let toks = match source_with_comments("//// rule\n/// doc", &mut symbols) {
+				Ok(ts) => ts,
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(toks[0].token, Token::Comment(TokenStr::from("// rule")));
+			assert_eq!(toks[1].token, Token::DocComment(TokenStr::from(" doc")));
+			assert_eq!(toks[1].span, Span { line: 2, column: 1, byte_offset: 10, len: 7 });
+		}
+
+		#[test]
+		fn test_options() {
+			let mut symbols = SymbolTable::default();
+			let input   = "#!/bin/ville\nlet x = 1 // one\nif\n";
+			let options = Options {
+				emit_comments:         true,
+				allow_shebang:         true,
+				keywords:              |word| (word == "if").then_some(Token::If),
+				newline_as_terminator: true,
+			};
+			let toks = match source_with(input, &mut symbols, options) {
+				Ok(ts) => ts.into_iter().map(|t| t.token).collect::<Vec<_>>(),
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(toks, vec![
+				Token::Shebang(TokenStr::from("/bin/ville")),
+				Token::Semicolon,
+				Token::Identifier(symbols.intern("let")),
+				Token::Identifier(symbols.intern("x")),
+				Token::Equal,
+				Token::Integer(TokenStr::from("1")),
+				Token::Comment(TokenStr::from(" one")),
+				Token::Semicolon,
+				Token::If,
+				Token::Semicolon,
+				Token::End,
+			]);
+
+			let crlf = "#!/bin/ville\r\nlet\tx = 1 // one\r\nif\r\n";
+			let crlf_toks = match source_with(crlf, &mut symbols, options) {
+				Ok(ts) => ts.into_iter().map(|t| t.token).collect::<Vec<_>>(),
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(crlf_toks, toks);
+
+			let strict = Options { allow_shebang: false, ..Options::default() };
+			let errs = match source_with(input, &mut symbols, strict) {
+				Ok(_)     => return assert_eq!(true, false),
+				Err(errs) => errs,
+			};
+			assert_eq!(errs[0].kind, ErrorKind::Terminal);
+			assert_eq!(errs[0].terminal, '#');
+		}
+
+		#[test]
+		fn test_reader() {
+			let mut symbols = SymbolTable::default();
+			let toks = match reader(&b"thing /= 18;"[..], &mut symbols) {
+				Ok(ts) => ts.tokens.into_iter().map(|t| t.token).collect::<Vec<_>>(),
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(toks, vec![
+				Token::Identifier(symbols.intern("thing")),
+				Token::SlashEqual,
+				Token::Integer(TokenStr::from("18")),
+				Token::Semicolon,
+				Token::End,
+			]);
+			assert!(matches!(reader(&b"x = #;"[..], &mut symbols), Err(Error::Scan(None, _))));
+			/* bad bytes alongside a real error fail the same way whatever the input came from */
+			assert!(matches!(reader(&b"x = \xff #;"[..], &mut symbols),
+				Err(Error::Scan(None, errs)) if errs[0].kind == ErrorKind::InvalidUtf8 && errs[1].kind == ErrorKind::Terminal));
+			assert!(matches!(reader(&b"x = \xff;"[..], &mut symbols),
+				Ok(scanned) if scanned.tokens.len() == 4 && scanned.invalid[0].kind == ErrorKind::InvalidUtf8));
+		}
+
+		#[test]
+		fn test_bytes() {
+			let mut symbols = SymbolTable::default();
+			let toks = match bytes(b"thing = \"\xc3\xa9\";", &mut symbols) {
+				Ok(ts) => ts.tokens.into_iter().map(|t| t.token).collect::<Vec<_>>(),
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(toks[2], Token::String(TokenStr::from("é")));
+
+			/* Latin-1 still scans: bad bytes decode as U+FFFD, spans stay offsets into the bytes */
+			let scanned = match bytes(b"caf\xe9 = \"na\xefve\";", &mut symbols) {
+				Ok(scanned) => scanned,
+				Err(_)      => return assert_eq!(true, false),
+			};
+			let found: Vec<(Token, Span)> = scanned.tokens.into_iter().map(|t| (t.token, t.span)).collect();
+			assert_eq!(found, vec![
+				(Token::Identifier(symbols.intern("caf")),           Span { line: 1, column: 1,  byte_offset: 0,  len: 3 }),
+				(Token::Equal,                                       Span { line: 1, column: 6,  byte_offset: 5,  len: 1 }),
+				(Token::String(TokenStr::from("na\u{fffd}ve")),      Span { line: 1, column: 8,  byte_offset: 7,  len: 7 }),
+				(Token::Semicolon,                                   Span { line: 1, column: 15, byte_offset: 14, len: 1 }),
+				(Token::End,                                         Span { line: 1, column: 16, byte_offset: 15, len: 0 }),
+			]);
+			let invalid: Vec<Span> = scanned.invalid.into_iter().map(|e| e.span).collect();
+			assert_eq!(invalid, vec![
+				Span { line: 1, column: 4,  byte_offset: 3,  len: 1 },
+				Span { line: 1, column: 11, byte_offset: 10, len: 1 },
+			]);
+
+			let errs = match bytes(b"a = \"\xff\";\nb\xe2\x82 # \xc3", &mut symbols) {
+				Ok(_)     => return assert_eq!(true, false),
+				Err(errs) => errs,
+			};
+			let found: Vec<(ErrorKind, Span)> = errs.into_iter().map(|e| (e.kind, e.span)).collect();
+			assert_eq!(found, vec![
+				(ErrorKind::InvalidUtf8, Span { line: 1, column: 6, byte_offset: 5,  len: 1 }),
+				(ErrorKind::InvalidUtf8, Span { line: 2, column: 2, byte_offset: 10, len: 2 }),
+				(ErrorKind::Terminal,    Span { line: 2, column: 4, byte_offset: 13, len: 1 }),
+				(ErrorKind::InvalidUtf8, Span { line: 2, column: 6, byte_offset: 15, len: 1 }),
+			]);
+		}
+
+		#[test]
+		fn test_lookahead() {
+			let mut symbols = SymbolTable::default();
+			let x = symbols.intern("x");
+			let cases = [
+				("!x", Token::Bang),
+				("=x", Token::Equal),
+				("-x", Token::Minus),
+				("<x", Token::Less),
+				("&x", Token::Ampersand),
+			];
+			for (input, op) in cases {
+				let toks = match source(input, &mut symbols) {
+					Ok(ts) => ts.into_iter().map(|t| t.token).collect::<Vec<_>>(),
+					Err(_) => return assert_eq!(true, false),
+				};
+				assert_eq!(toks, vec![op, Token::Identifier(x), Token::End]);
+			}
+		}
+
+		#[test]
+		fn test_file_errors() {
+			let mut symbols = SymbolTable::default();
+			let missing = Path::new("tests/missing.lang");
+			assert!(matches!(file(missing, &mut symbols), Err(Error::NotFound(p)) if p == missing));
+			assert!(matches!(file("tests", &mut symbols), Err(Error::File(p, _)) if p == Path::new("tests")));
+
+			let latin = Path::new("tests/latin.lang");
+			let scanned = match file(latin, &mut symbols) {
+				Ok(scanned) => scanned,
+				Err(_)      => return assert_eq!(true, false),
+			};
+			let invalid: Vec<String> = scanned.invalid.iter().map(|e| e.to_string()).collect();
+			assert_eq!(invalid, vec!["1:4: invalid UTF-8", "2:11: invalid UTF-8"]);
+			assert_eq!(scanned.tokens[6].token, Token::String(TokenStr::from("na\u{fffd}ve")));
+
+			let scan = match file("tests/unexpected.lang", &mut symbols) {
+				Err(e @ Error::Scan(Some(_), _)) => e,
+				_                                => return assert_eq!(true, false),
+			};
+			assert_eq!(scan.to_string(), "tests/unexpected.lang:1:9: unexpected character '#'");
+
+			let denied = file_error(latin, io::Error::from(io::ErrorKind::PermissionDenied));
+			assert!(matches!(&denied, Error::PermissionDenied(p) if p == latin));
+			assert_eq!(denied.to_string(), "tests/latin.lang: permission denied");
+		}
+
+		#[test]
+		fn test_error_display() {
+			use std::error::Error as _;
+
+			let errors = match source("x = #;\n\"open", &mut SymbolTable::default()) {
+				Ok(_)     => return assert_eq!(true, false),
+				Err(errs) => errs,
+			};
+			assert_eq!(Error::Scan(None, errors).to_string(),
+				"1:5: unexpected character '#'\n2:1: unterminated string");
+
+			let missing = Error::NotFound(PathBuf::from("tests/missing.lang"));
+			assert_eq!(missing.to_string(), "tests/missing.lang: no such file");
+			assert!(missing.source().is_none());
+
+			let read = Error::Read(io::Error::other("reset"));
+			assert_eq!(read.to_string(), "failed to read input: reset");
+			assert_eq!(read.source().map(|e| e.to_string()), Some(String::from("reset")));
+		}
+
+		#[test]
+		fn test_punctuation() {
+			let mut symbols = SymbolTable::default();
+			let toks = match source("f(a, b.c): [d];", &mut symbols) {
+				Ok(ts) => ts.into_iter().map(|t| t.token).collect::<Vec<_>>(),
+				Err(_) => return assert_eq!(true, false),
+			};
+			assert_eq!(toks, vec![
+				Token::Identifier(symbols.intern("f")),
+				Token::LeftParen,
+				Token::Identifier(symbols.intern("a")),
+				Token::Comma,
+				Token::Identifier(symbols.intern("b")),
+				Token::Dot,
+				Token::Identifier(symbols.intern("c")),
+				Token::RightParen,
+				Token::Colon,
+				Token::LeftBracket,
+				Token::Identifier(symbols.intern("d")),
+				Token::RightBracket,
+				Token::Semicolon,
+				Token::End,
+			]);
+		}
+
+		/* Not run by default: `cargo test --release --lib bench_source -- --ignored --nocapture`
+		 * scans about 8 MB of mixed code and prints the best of a few runs */
+		#[test]
+		#[ignore]
+		fn bench_source() {
+			let line  = "thing = other * 1234 + 5.5e3; name = \"some text\"; // note\n";
+			let input = line.repeat(8 * 1024 * 1024 / line.len());
+
+			let mut best   = std::time::Duration::MAX;
+			let mut tokens = 0;
+			for _ in 0..5 {
+				let mut symbols = SymbolTable::default();
+				let start = std::time::Instant::now();
+				let toks  = match source(&input, &mut symbols) {
+					Ok(ts) => ts,
+					Err(_) => return assert_eq!(true, false),
+				};
+				best   = best.min(start.elapsed());
+				tokens = toks.len();
+			}
+			eprintln!("scanned {} bytes into {} tokens in {:?}", input.len(), tokens, best);
+		}
 }
@@ -0,0 +1,5 @@
+//
+// Created by Justin Tunheim on 3/21/25
+//
+
+pub mod lang;
@@ -2,7 +2,5 @@
 // Created by Justin Tunheim on 3/21/25
 //
 
-mod lang;
-
 fn main() {
 }